@@ -0,0 +1,30 @@
+//! How long a long game takes to play, with the snake growing every tick,
+//! and that it ends with as many meshes, materials and animations as it
+//! started with however long the snake gets.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use snake::headless::{asset_counts, headless_game, tick_growing};
+
+const TICKS: usize = 1000;
+
+fn long_game(c: &mut Criterion) {
+    let mut group = c.benchmark_group("long_game");
+    group.sample_size(10);
+    group.bench_function("growing every tick", |b| {
+        b.iter_batched(
+            headless_game,
+            |mut app| {
+                let before = asset_counts(&app);
+                for _ in 0..TICKS {
+                    tick_growing(&mut app);
+                }
+                assert_eq!(before, asset_counts(&app), "assets grew with the snake");
+                app
+            },
+            BatchSize::PerIteration,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, long_game);
+criterion_main!(benches);
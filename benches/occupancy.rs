@@ -0,0 +1,57 @@
+//! Looking up whether a cell holds a segment in the occupancy grid, against
+//! scanning every segment's `Transform` as collision checks and food placement
+//! used to.
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use snake::occupancy::Occupancy;
+
+const SIDE: i32 = 1000;
+const SNAKE: i32 = 200_000;
+const QUERIES: i32 = 1000;
+const SEGMENT_SIZE: f32 = 10.;
+
+fn lookup_against_scan(c: &mut Criterion) {
+    let body: Vec<IVec2> = (0..SNAKE).map(|i| IVec2::new(i % SIDE, i / SIDE)).collect();
+    let queries: Vec<IVec2> = (0..QUERIES)
+        .map(|i| IVec2::new(i * 7 % SIDE, i * 13 % SIDE))
+        .collect();
+
+    let mut world = World::new();
+    world.spawn_batch(
+        body.iter()
+            .map(|cell| Transform::from_translation((cell.as_vec2() * SEGMENT_SIZE).extend(0.))),
+    );
+    let mut transforms = world.query::<&Transform>();
+    let mut occupancy = Occupancy::new(IVec2::ZERO, IVec2::splat(SIDE - 1));
+    for &cell in &body {
+        occupancy.add_segment(cell);
+    }
+
+    let mut group = c.benchmark_group("occupied_cells");
+    group.sample_size(10);
+    group.bench_function("scan transforms", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .filter(|&&cell| {
+                    let at = cell.as_vec2() * SEGMENT_SIZE;
+                    transforms
+                        .iter(&world)
+                        .any(|transform| transform.translation.truncate() == at)
+                })
+                .count()
+        });
+    });
+    group.bench_function("occupancy grid", |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .filter(|&&cell| black_box(&occupancy).segments_at(cell) > 0)
+                .count()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup_against_scan);
+criterion_main!(benches);
@@ -0,0 +1,197 @@
+//! Keeping the snake in view in arenas bigger than the window: the camera
+//! follows the head without ever showing past the walls, and a minimap in
+//! the corner shows the whole arena.
+use bevy::{
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+};
+
+use crate::occupancy::Occupancy;
+use crate::{cell_to_world, GameState, PlayerControlled, Segments, SEGMENT_SIZE};
+
+/// How quickly the camera closes the gap to the head, as a fraction per
+/// second of the distance left.
+const FOLLOW_RATE: f32 = 5.;
+/// The longer side of the minimap, in logical pixels.
+const MINIMAP_SIZE: f32 = 180.;
+/// Gap between the minimap and the corner of the window.
+const MINIMAP_MARGIN: f32 = 10.;
+const MINIMAP_BACKGROUND: Color = Color::srgb(0.02, 0.02, 0.04);
+
+/// Where the camera is looking, before any screen shake.
+#[derive(Component, Default)]
+pub struct CameraFocus(Vec2);
+
+/// The camera that draws the minimap.
+#[derive(Component)]
+pub struct Minimap;
+
+/// The world-space rectangle covered by the arena's cells.
+fn arena_rect(occupancy: &Occupancy) -> Rect {
+    let (min, max) = occupancy.bounds();
+    let half_cell = Vec2::splat(SEGMENT_SIZE / 2.);
+    Rect::from_corners(
+        cell_to_world(min) - half_cell,
+        cell_to_world(max) + half_cell,
+    )
+}
+
+/// Moves `target` as little as possible to keep a view `size` across inside
+/// `arena`. Along a side where the arena is smaller than the view, it is
+/// centered instead.
+fn clamp_view(target: Vec2, arena: Rect, size: Vec2) -> Vec2 {
+    let half = size / 2.;
+    let center = arena.center();
+    let clamp = |target: f32, min: f32, max: f32, half: f32, center: f32| {
+        if max - min <= half * 2. {
+            center
+        } else {
+            target.clamp(min + half, max - half)
+        }
+    };
+    Vec2::new(
+        clamp(target.x, arena.min.x, arena.max.x, half.x, center.x),
+        clamp(target.y, arena.min.y, arena.max.y, half.y, center.y),
+    )
+}
+
+/// Glides the camera after the player's head. The camera lands on whole
+/// pixels, so the grid stays crisp while it moves.
+pub fn follow_snake(
+    time: Res<Time>,
+    occupancy: Option<Res<Occupancy>>,
+    windows: Query<&Window>,
+    snakes: Query<&Segments, With<PlayerControlled>>,
+    transforms: Query<&Transform, Without<CameraFocus>>,
+    mut cameras: Query<(&mut CameraFocus, &mut Transform)>,
+) {
+    let head = snakes
+        .iter()
+        .next()
+        .and_then(|segments| transforms.get(segments.head()).ok());
+    let view = windows.iter().next().map(|window| window.size());
+    for (mut focus, mut transform) in &mut cameras {
+        if let (Some(head), Some(occupancy), Some(view)) = (head, &occupancy, view) {
+            let target = clamp_view(head.translation.truncate(), arena_rect(occupancy), view);
+            let t = 1. - (-FOLLOW_RATE * time.delta_secs()).exp();
+            focus.0 = focus.0.lerp(target, t);
+        }
+        let at = focus.0.round();
+        transform.translation.x = at.x;
+        transform.translation.y = at.y;
+    }
+}
+
+/// Puts the camera straight onto where a new game starts, rather than
+/// gliding over from wherever the last one ended.
+pub fn reset_camera_focus(
+    occupancy: Res<Occupancy>,
+    windows: Query<&Window>,
+    mut cameras: Query<&mut CameraFocus>,
+) {
+    let Some(view) = windows.iter().next().map(Window::size) else {
+        return;
+    };
+    let start = clamp_view(cell_to_world(IVec2::ZERO), arena_rect(&occupancy), view);
+    for mut focus in &mut cameras {
+        focus.0 = start;
+    }
+}
+
+pub fn setup_minimap(mut commands: Commands) {
+    commands.spawn((
+        Name::new("minimap"),
+        Minimap,
+        Camera2d,
+        Camera {
+            // Drawn over the game, and only while there is a game to show.
+            order: 1,
+            is_active: false,
+            hdr: true,
+            clear_color: ClearColorConfig::Custom(MINIMAP_BACKGROUND),
+            ..default()
+        },
+    ));
+}
+
+/// Shows the minimap during a game, fitted to the arena in the bottom left
+/// corner of the window.
+pub fn update_minimap(
+    state: Res<State<GameState>>,
+    occupancy: Option<Res<Occupancy>>,
+    windows: Query<&Window>,
+    mut minimaps: Query<(&mut Camera, &mut Projection, &mut Transform), With<Minimap>>,
+) {
+    let playing = matches!(
+        state.get(),
+        GameState::InGame | GameState::Pause | GameState::Dying
+    );
+    let window = windows.iter().next();
+    for (mut camera, mut projection, mut transform) in &mut minimaps {
+        let (Some(occupancy), Some(window), true) = (&occupancy, window, playing) else {
+            if camera.is_active {
+                camera.is_active = false;
+            }
+            continue;
+        };
+        let arena = arena_rect(occupancy);
+        let size = arena.size() * MINIMAP_SIZE / arena.size().max_element();
+        let scale = window.scale_factor();
+        let physical_size = (size * scale).as_uvec2().max(UVec2::ONE);
+        let margin = (MINIMAP_MARGIN * scale) as u32;
+        let viewport = Viewport {
+            physical_position: UVec2::new(
+                margin,
+                window
+                    .physical_height()
+                    .saturating_sub(margin + physical_size.y),
+            ),
+            physical_size,
+            ..default()
+        };
+        let placed = camera.viewport.as_ref().is_some_and(|placed| {
+            placed.physical_position == viewport.physical_position
+                && placed.physical_size == viewport.physical_size
+        });
+        if !camera.is_active || !placed {
+            camera.is_active = true;
+            camera.viewport = Some(viewport);
+        }
+        if let Projection::Orthographic(ortho) = &mut *projection {
+            let fitted = matches!(ortho.scaling_mode, ScalingMode::Fixed { width, height }
+                if width == arena.width() && height == arena.height());
+            if !fitted {
+                ortho.scaling_mode = ScalingMode::Fixed {
+                    width: arena.width(),
+                    height: arena.height(),
+                };
+            }
+        }
+        let center = arena.center().extend(transform.translation.z);
+        if transform.translation != center {
+            transform.translation = center;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_stays_inside_the_arena() {
+        let arena = Rect::new(-500., -300., 500., 300.);
+        let view = Vec2::new(400., 200.);
+        assert_eq!(clamp_view(Vec2::ZERO, arena, view), Vec2::ZERO);
+        assert_eq!(
+            clamp_view(Vec2::new(450., -290.), arena, view),
+            Vec2::new(300., -200.)
+        );
+        // Too narrow to scroll across, so it stays centered that way.
+        let narrow = Rect::new(-100., -300., 300., 300.);
+        assert_eq!(
+            clamp_view(Vec2::new(250., 250.), narrow, Vec2::new(600., 200.)),
+            Vec2::new(100., 200.)
+        );
+    }
+}
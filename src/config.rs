@@ -0,0 +1,80 @@
+//! Settings that persist between runs, kept in a RON file in the working
+//! directory, and the helpers other files kept there share.
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::input::InputMap;
+use crate::options::GameOptions;
+use crate::pointer::PointerSteering;
+
+const CONFIG_PATH: &str = "snake.config.ron";
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Config {
+    input: InputMap,
+    pointer_steering: PointerSteering,
+    options: GameOptions,
+}
+
+/// Reads the config file, falling back to defaults when it is missing or
+/// can't be parsed.
+pub fn load_config(mut commands: Commands) {
+    let config: Config = load_ron(CONFIG_PATH);
+    commands.insert_resource(config.input);
+    commands.insert_resource(config.pointer_steering);
+    commands.insert_resource(config.options);
+}
+
+/// Writes the config file whenever a setting changes.
+pub fn save_config(
+    input: Res<InputMap>,
+    pointer_steering: Res<PointerSteering>,
+    options: Res<GameOptions>,
+) {
+    if !(edited(&input) || edited(&pointer_steering) || edited(&options)) {
+        return;
+    }
+    let config = Config {
+        input: input.clone(),
+        pointer_steering: *pointer_steering,
+        options: options.clone(),
+    };
+    save_ron(CONFIG_PATH, &config);
+}
+
+/// Reads a RON file in the working directory, falling back to the default
+/// when it is missing or can't be parsed.
+pub fn load_ron<T: DeserializeOwned + Default>(path: &str) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Ignoring {path}: {err}");
+            T::default()
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => T::default(),
+        Err(err) => {
+            warn!("Couldn't read {path}: {err}");
+            T::default()
+        }
+    }
+}
+
+/// Writes a RON file in the working directory, warning if it can't.
+pub fn save_ron<T: Serialize>(path: &str, value: &T) {
+    let text = match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(err) => {
+            warn!("Couldn't save {path}: {err}");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::write(path, text) {
+        warn!("Couldn't write {path}: {err}");
+    }
+}
+
+/// Whether a resource read from a file has changed since, and so needs
+/// writing back.
+pub fn edited<R: Resource>(resource: &Res<R>) -> bool {
+    resource.is_changed() && !resource.is_added()
+}
@@ -0,0 +1,132 @@
+//! The controls screen, where each action can be bound to a new key.
+use bevy::prelude::*;
+
+use crate::input::{Action, Actions, InputMap};
+use crate::menu::{relabel, MenuAction, MenuItemLabel, MenuSpec, MenuText};
+use crate::pointer::PointerSteering;
+use crate::theme::CurrentTheme;
+use crate::GameState;
+
+#[derive(Component)]
+pub struct StatusText;
+
+/// The action waiting for a key press, if any.
+#[derive(Resource, Default)]
+pub struct Rebinding(Option<Action>);
+
+pub fn setup_controls(
+    mut commands: Commands,
+    theme: Res<CurrentTheme>,
+    input: Res<InputMap>,
+    pointer: Res<PointerSteering>,
+) {
+    commands.insert_resource(Rebinding::default());
+    let mut spec = MenuSpec::new(Some("Controls"));
+    for action in Action::ALL {
+        spec = spec.item(
+            binding_text(action, &input, None),
+            MenuAction::Rebind(action),
+        );
+    }
+    let screen = spec
+        .item(pointer_text(&pointer), MenuAction::TogglePointerSteering)
+        .item("Back", MenuAction::MainMenu)
+        .spawn(&mut commands, &theme);
+    commands.entity(screen).with_child((
+        StatusText,
+        MenuText,
+        Text::new(""),
+        theme.font.clone(),
+        TextColor(theme.text),
+        Node {
+            margin: UiRect::top(Val::Px(10.)),
+            ..default()
+        },
+    ));
+}
+
+fn binding_text(action: Action, input: &InputMap, rebinding: Option<Action>) -> String {
+    if rebinding == Some(action) {
+        format!("{}: press a key", action.label())
+    } else {
+        format!("{}: {}", action.label(), input.describe(action))
+    }
+}
+
+fn pointer_text(pointer: &PointerSteering) -> String {
+    format!("Swipe steering: {}", if pointer.0 { "On" } else { "Off" })
+}
+
+/// Binds the next key pressed to the action being rebound.
+pub fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut input: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut status: Query<&mut Text, With<StatusText>>,
+) {
+    // Skip the frame the button was pressed, or the Confirm key that pressed
+    // it would be taken as the new binding.
+    let Some(action) = rebinding.0.filter(|_| !rebinding.is_changed()) else {
+        return;
+    };
+    let Some(&key) = keys.get_just_pressed().next() else {
+        return;
+    };
+    let message = match input.bind(action, key) {
+        Ok(()) => String::new(),
+        Err(other) => format!("{key:?} is already used for {}", other.label()),
+    };
+    for mut text in &mut status {
+        text.0.clone_from(&message);
+    }
+    rebinding.0 = None;
+}
+
+pub fn controls_actions(
+    mut menu_actions: EventReader<MenuAction>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut pointer: ResMut<PointerSteering>,
+    actions: Actions,
+) {
+    for action in menu_actions.read() {
+        match *action {
+            // Pressing the button again cancels.
+            MenuAction::Rebind(action) if rebinding.0 == Some(action) => rebinding.0 = None,
+            MenuAction::Rebind(action) => rebinding.0 = Some(action),
+            MenuAction::TogglePointerSteering => pointer.0 = !pointer.0,
+            _ => {}
+        }
+    }
+    if rebinding.0.is_none() && actions.just_pressed(Action::Back) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+/// Whether no action is waiting for a key, so keys can be used to move
+/// around the menus.
+pub fn rebinding_idle(rebinding: Option<Res<Rebinding>>) -> bool {
+    rebinding.is_none_or(|rebinding| rebinding.0.is_none())
+}
+
+/// Keeps the button labels in step with the settings.
+pub fn refresh_binding_labels(
+    input: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    pointer: Res<PointerSteering>,
+    mut labels: Query<(&mut Text, &MenuItemLabel)>,
+) {
+    if input.is_changed() || rebinding.is_changed() {
+        for action in Action::ALL {
+            let text = binding_text(action, &input, rebinding.0);
+            relabel(&mut labels, MenuAction::Rebind(action), &text);
+        }
+    }
+    if pointer.is_changed() {
+        relabel(
+            &mut labels,
+            MenuAction::TogglePointerSteering,
+            &pointer_text(&pointer),
+        );
+    }
+}
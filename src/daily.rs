@@ -0,0 +1,230 @@
+//! The daily challenge: a level, game mode and food sequence picked from the
+//! date, the same for everyone playing that day. The first attempt each day
+//! is the official one; later ones only count towards the day's best. Results
+//! are kept in a RON file in the working directory. The player's own mode and
+//! level are put back on returning to the main menu.
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{edited, load_ron, save_ron};
+use crate::level::Levels;
+use crate::menu::MenuAction;
+use crate::mode::GameMode;
+use crate::score::Score;
+use crate::GameState;
+
+const RECORDS_PATH: &str = "snake.daily.ron";
+
+/// How many cells a challenge's arena spans across and up when its level
+/// doesn't say.
+pub const DAILY_ARENA: [i32; 2] = [128, 72];
+
+/// Modes a challenge can be played in. Zen is left out, as a game that can't
+/// end makes for a poor contest.
+const CHALLENGE_MODES: [GameMode; 3] =
+    [GameMode::Classic, GameMode::TimeAttack, GameMode::Survival];
+
+/// Days since the Unix epoch, in UTC so the day changes at the same moment
+/// for everyone.
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400)
+}
+
+/// The `YYYY-MM-DD` date of a day since the Unix epoch.
+fn date(day: u64) -> String {
+    // Howard Hinnant's days_from_civil, run backwards.
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// Scrambles the day number, so neighbouring days get unrelated seeds.
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// What a day's challenge is played on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Challenge {
+    day: u64,
+    seed: u64,
+    level: usize,
+    mode: GameMode,
+}
+
+impl Challenge {
+    fn for_day(day: u64, level_count: usize) -> Self {
+        let seed = splitmix64(day);
+        Challenge {
+            day,
+            seed,
+            level: (seed % level_count.max(1) as u64) as usize,
+            mode: CHALLENGE_MODES[(seed >> 32) as usize % CHALLENGE_MODES.len()],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+struct DayRecord {
+    /// The score of the first attempt, once it has started.
+    official: Option<u32>,
+    /// The best score of any attempt that day.
+    best: u32,
+}
+
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DailyRecords {
+    days: BTreeMap<u64, DayRecord>,
+}
+
+/// Present while a daily challenge is being played.
+#[derive(Resource)]
+pub struct DailyAttempt {
+    pub day: u64,
+    /// Seeds what appears during the game, so every attempt that day gets
+    /// the same sequence.
+    pub seed: u64,
+    pub official: bool,
+}
+
+/// The mode and level the player had picked before starting a challenge.
+#[derive(Resource)]
+pub struct SavedSelection {
+    mode: GameMode,
+    level: usize,
+}
+
+pub fn load_daily_records(mut commands: Commands) {
+    commands.insert_resource(load_ron::<DailyRecords>(RECORDS_PATH));
+}
+
+/// Writes the records file whenever a result is recorded.
+pub fn save_daily_records(records: Res<DailyRecords>) {
+    if edited(&records) {
+        save_ron(RECORDS_PATH, &*records);
+    }
+}
+
+/// The menu button for today's challenge, with today's results once played.
+pub fn daily_label(records: &DailyRecords) -> String {
+    let day = today();
+    match records.days.get(&day) {
+        Some(DayRecord {
+            official: Some(official),
+            best,
+        }) => format!("Daily {}: {official} (best {best})", date(day)),
+        _ => format!("Daily {}", date(day)),
+    }
+}
+
+/// Starts today's challenge from the main menu.
+pub fn daily_actions(
+    mut commands: Commands,
+    mut actions: EventReader<MenuAction>,
+    mut records: ResMut<DailyRecords>,
+    mut mode: ResMut<GameMode>,
+    mut levels: ResMut<Levels>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for action in actions.read() {
+        if *action != MenuAction::DailyChallenge {
+            continue;
+        }
+        let challenge = Challenge::for_day(today(), levels.count());
+        let record = records.days.entry(challenge.day).or_default();
+        // Starting is what uses up the official attempt, so quitting part way
+        // through doesn't earn another.
+        let official = record.official.is_none();
+        if official {
+            record.official = Some(0);
+        }
+        commands.insert_resource(SavedSelection {
+            mode: *mode,
+            level: levels.selected_index(),
+        });
+        *mode = challenge.mode;
+        levels.select(challenge.level);
+        commands.insert_resource(DailyAttempt {
+            day: challenge.day,
+            seed: challenge.seed,
+            official,
+        });
+        next_state.set(GameState::StartGame);
+    }
+}
+
+/// Leaves the challenge on returning to the main menu, putting the player's
+/// own mode and level back.
+pub fn end_daily(
+    mut commands: Commands,
+    saved: Option<Res<SavedSelection>>,
+    mut mode: ResMut<GameMode>,
+    mut levels: ResMut<Levels>,
+) {
+    if let Some(saved) = saved {
+        *mode = saved.mode;
+        levels.select(saved.level);
+        commands.remove_resource::<SavedSelection>();
+    }
+    commands.remove_resource::<DailyAttempt>();
+}
+
+/// Records the score of a daily challenge when the game ends. Retrying from
+/// there plays the challenge again, but never as the official attempt.
+pub fn record_daily(
+    attempt: Option<ResMut<DailyAttempt>>,
+    score: Res<Score>,
+    mut records: ResMut<DailyRecords>,
+) {
+    let Some(mut attempt) = attempt else {
+        return;
+    };
+    let record = records.days.entry(attempt.day).or_default();
+    if attempt.official {
+        record.official = Some(score.0);
+        attempt.official = false;
+    }
+    record.best = record.best.max(score.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_follow_the_date() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(20_744), "2026-10-18");
+        assert_eq!(date(11_016), "2000-02-29");
+
+        let day = 20_744;
+        assert_eq!(Challenge::for_day(day, 3), Challenge::for_day(day, 3));
+        let challenges: Vec<Challenge> = (day..day + 30)
+            .map(|day| Challenge::for_day(day, 3))
+            .collect();
+        assert!(challenges.iter().all(|challenge| challenge.level < 3));
+        assert!(challenges
+            .windows(2)
+            .any(|pair| pair[0].level != pair[1].level || pair[0].mode != pair[1].mode));
+        assert!(challenges
+            .iter()
+            .all(|challenge| challenge.mode != GameMode::Zen));
+    }
+}
@@ -0,0 +1,247 @@
+//! Keyboard and gamepad focus for menu buttons.
+use bevy::prelude::*;
+
+use crate::input::{Action, Actions};
+use crate::menu::{MenuAction, MenuItem};
+use crate::options::{sound_effect, GameOptions};
+use crate::theme::CurrentTheme;
+use crate::MenuRolloverSound;
+
+/// The button that Confirm activates.
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    focused: Option<Entity>,
+    /// A button pressed from the keyboard, released again on the next frame
+    /// the way a mouse click would be.
+    pressed: Option<Entity>,
+}
+
+/// Moves focus between the visible buttons, top to bottom, and presses the
+/// focused one on Confirm. Left and Right step a focused setting instead of
+/// moving. Runs after the UI has worked out mouse interactions so the menus
+/// see both the same way.
+pub fn navigate_focus(
+    mut commands: Commands,
+    mut focus: ResMut<MenuFocus>,
+    mut menu_actions: EventWriter<MenuAction>,
+    actions: Actions,
+    keys: Res<ButtonInput<KeyCode>>,
+    menu_sound: Res<MenuRolloverSound>,
+    options: Res<GameOptions>,
+    mut buttons: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &InheritedVisibility,
+            &mut Interaction,
+        ),
+        With<Button>,
+    >,
+    items: Query<&MenuItem>,
+) {
+    if let Some(pressed) = focus.pressed.take() {
+        if let Ok((.., mut interaction)) = buttons.get_mut(pressed) {
+            interaction.set_if_neq(Interaction::None);
+        }
+    }
+
+    let mut visible: Vec<(Entity, Vec3)> = buttons
+        .iter()
+        .filter(|(_, _, visibility, _)| visibility.get())
+        .map(|(entity, transform, ..)| (entity, transform.translation()))
+        .collect();
+    visible.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    // Hovering a button with the mouse focuses it too.
+    for (entity, .., interaction) in &mut buttons {
+        if interaction.is_changed() && *interaction == Interaction::Hovered {
+            focus.focused = Some(entity);
+        }
+    }
+
+    let index = focus
+        .focused
+        .and_then(|focused| visible.iter().position(|(entity, _)| *entity == focused));
+    let Some(index) = index else {
+        focus.focused = visible.first().map(|(entity, _)| *entity);
+        return;
+    };
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let tab = keys.just_pressed(KeyCode::Tab);
+    // Whether Left and Right step a setting is decided by the button focused
+    // before they move anything.
+    let setting = match items.get(visible[index].0) {
+        Ok(MenuItem(MenuAction::Adjust(setting))) => Some(*setting),
+        _ => None,
+    };
+    let left = actions.just_pressed(Action::Left);
+    let right = actions.just_pressed(Action::Right);
+    if let Some(setting) = setting {
+        if left {
+            menu_actions.write(MenuAction::AdjustDown(setting));
+        } else if right {
+            menu_actions.write(MenuAction::Adjust(setting));
+        }
+    }
+    let left = left && setting.is_none();
+    let right = right && setting.is_none();
+    let step: isize = if actions.just_pressed(Action::Up) || left || (tab && shift) {
+        -1
+    } else if actions.just_pressed(Action::Down) || right || tab {
+        1
+    } else {
+        0
+    };
+    if step != 0 && visible.len() > 1 {
+        let next = (index as isize + step).rem_euclid(visible.len() as isize) as usize;
+        focus.focused = Some(visible[next].0);
+        commands.spawn(sound_effect(&menu_sound.0, &options));
+    }
+
+    if actions.just_pressed(Action::Confirm) {
+        if let Some(focused) = focus.focused {
+            if let Ok((.., mut interaction)) = buttons.get_mut(focused) {
+                *interaction = Interaction::Pressed;
+                focus.pressed = Some(focused);
+            }
+        }
+    }
+}
+
+/// Colors every button for its interaction, with the focused one drawn as if
+/// hovered.
+pub fn highlight_focus(
+    focus: Res<MenuFocus>,
+    theme: Res<CurrentTheme>,
+    mut buttons: Query<(Entity, &Interaction, &mut BackgroundColor), With<Button>>,
+) {
+    for (entity, interaction, mut color) in &mut buttons {
+        let wanted = match interaction {
+            Interaction::Pressed => theme.pressed_button,
+            Interaction::Hovered => theme.hovered_button,
+            Interaction::None if focus.focused == Some(entity) => theme.hovered_button,
+            Interaction::None => theme.normal_button,
+        };
+        color.set_if_neq(BackgroundColor(wanted));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputMap;
+    use crate::options::Setting;
+
+    fn press(app: &mut App, key: KeyCode) {
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(key);
+        app.update();
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(key);
+        keys.clear();
+    }
+
+    fn menu_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<InputMap>()
+            .init_resource::<MenuFocus>()
+            .insert_resource(MenuRolloverSound(Handle::default()))
+            .init_resource::<GameOptions>()
+            .add_event::<MenuAction>()
+            .add_systems(Update, navigate_focus);
+        app
+    }
+
+    #[test]
+    fn keys_move_focus_and_press_the_focused_button() {
+        let mut app = menu_app();
+        let mut button = |y: f32, visible: bool| {
+            app.world_mut()
+                .spawn((
+                    Button,
+                    Interaction::None,
+                    GlobalTransform::from_xyz(0., y, 0.),
+                    if visible {
+                        InheritedVisibility::VISIBLE
+                    } else {
+                        InheritedVisibility::HIDDEN
+                    },
+                ))
+                .id()
+        };
+        // Spawned out of screen order, with one that can't be seen.
+        let bottom = button(200., true);
+        let hidden = button(100., false);
+        let top = button(0., true);
+        let focused = |app: &App| app.world().resource::<MenuFocus>().focused;
+
+        app.update();
+        assert_eq!(focused(&app), Some(top));
+        press(&mut app, KeyCode::ArrowDown);
+        assert_eq!(focused(&app), Some(bottom));
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(focused(&app), Some(top));
+        press(&mut app, KeyCode::ArrowUp);
+        assert_eq!(focused(&app), Some(bottom));
+
+        press(&mut app, KeyCode::Enter);
+        let interaction = |app: &App, e| *app.world().get::<Interaction>(e).unwrap();
+        assert_eq!(interaction(&app, bottom), Interaction::Pressed);
+        app.update();
+        assert_eq!(interaction(&app, bottom), Interaction::None);
+        assert_eq!(interaction(&app, hidden), Interaction::None);
+    }
+
+    #[test]
+    fn left_and_right_step_only_the_setting_focused_before_they_move() {
+        let mut app = menu_app();
+        let mut button = |y: f32, action: MenuAction| {
+            app.world_mut()
+                .spawn((
+                    Button,
+                    Interaction::None,
+                    GlobalTransform::from_xyz(0., y, 0.),
+                    InheritedVisibility::VISIBLE,
+                    MenuItem(action),
+                ))
+                .id()
+        };
+        let volume = button(0., MenuAction::Adjust(Setting::MasterVolume));
+        let style = button(100., MenuAction::Adjust(Setting::SegmentStyle));
+        let back = button(200., MenuAction::Back);
+        let focused = |app: &App| app.world().resource::<MenuFocus>().focused;
+        let sent = |app: &mut App| {
+            app.world_mut()
+                .resource_mut::<Events<MenuAction>>()
+                .drain()
+                .collect::<Vec<_>>()
+        };
+
+        app.update();
+        app.world_mut().resource_mut::<MenuFocus>().focused = Some(back);
+        // Back moves up onto a setting, and wraps round to the top one,
+        // without stepping either.
+        press(&mut app, KeyCode::ArrowLeft);
+        assert_eq!(focused(&app), Some(style));
+        assert_eq!(sent(&mut app), []);
+        app.world_mut().resource_mut::<MenuFocus>().focused = Some(back);
+        press(&mut app, KeyCode::ArrowRight);
+        assert_eq!(focused(&app), Some(volume));
+        assert_eq!(sent(&mut app), []);
+
+        // A focused setting is stepped and keeps focus.
+        press(&mut app, KeyCode::ArrowLeft);
+        assert_eq!(focused(&app), Some(volume));
+        assert_eq!(
+            sent(&mut app),
+            [MenuAction::AdjustDown(Setting::MasterVolume)]
+        );
+        press(&mut app, KeyCode::ArrowRight);
+        assert_eq!(focused(&app), Some(volume));
+        assert_eq!(sent(&mut app), [MenuAction::Adjust(Setting::MasterVolume)]);
+    }
+}
@@ -0,0 +1,354 @@
+//! Hazards placed by the level: patrolling blocks, spikes that come and go,
+//! and portals. They move in step with the snake, once per tick. Survival
+//! games also scatter more and more obstacles around the arena.
+use bevy::prelude::*;
+
+use crate::level::{cell, Level, Levels};
+use crate::occupancy::Occupancy;
+use crate::{
+    cell_to_world, CleanupOnRestart, GameRng, GridPosition, MoveTimer, PlayerControlled, Segments,
+    SnakeAssets,
+};
+
+/// How big a retracted spike is drawn, compared to a raised one.
+const RETRACTED_SCALE: f32 = 0.4;
+/// How often a Survival game adds an obstacle.
+const OBSTACLE_SPAWN_SECS: f32 = 4.;
+/// Obstacles never appear this many cells or fewer from a snake's head, so
+/// there is always time to steer round them.
+const OBSTACLE_CLEARANCE: i32 = 4;
+
+/// A block walking along `path`, one cell per tick. It turns back at the
+/// ends, unless the path is a loop.
+#[derive(Component)]
+pub struct Patrol {
+    path: Vec<IVec2>,
+    looped: bool,
+    index: usize,
+    forward: bool,
+}
+
+impl Patrol {
+    /// A patrol along straight lines between `corners`, starting at the first.
+    pub fn new(corners: &[IVec2]) -> Self {
+        let mut path = corners.first().copied().into_iter().collect::<Vec<_>>();
+        for pair in corners.windows(2) {
+            let mut at = pair[0];
+            while at != pair[1] {
+                at += (pair[1] - at).signum()
+                    * if at.x != pair[1].x {
+                        IVec2::X
+                    } else {
+                        IVec2::Y
+                    };
+                path.push(at);
+            }
+        }
+        // A loop walks round and round rather than turning back, and doesn't
+        // need its starting cell twice.
+        let looped = path.len() > 2 && path.first() == path.last();
+        if looped {
+            path.pop();
+        }
+        Patrol {
+            path,
+            looped,
+            index: 0,
+            forward: true,
+        }
+    }
+
+    pub fn cell(&self) -> IVec2 {
+        self.path[self.index]
+    }
+
+    fn step(&mut self) {
+        let len = self.path.len();
+        if len < 2 {
+            return;
+        }
+        if self.looped {
+            self.index = (self.index + 1) % len;
+            return;
+        }
+        if self.forward && self.index + 1 == len {
+            self.forward = false;
+        } else if !self.forward && self.index == 0 {
+            self.forward = true;
+        }
+        if self.forward {
+            self.index += 1;
+        } else {
+            self.index -= 1;
+        }
+    }
+}
+
+/// A cell that is deadly while raised.
+#[derive(Component)]
+pub struct Spike {
+    on: u32,
+    off: u32,
+    tick: u32,
+}
+
+impl Spike {
+    pub fn raised(&self) -> bool {
+        self.tick % (self.on + self.off).max(1) < self.on
+    }
+}
+
+/// One end of a pair of portals. Where it leads is kept in [`Occupancy`].
+#[derive(Component)]
+pub struct Portal;
+
+#[derive(Resource)]
+pub struct HazardAssets {
+    patrol: Handle<ColorMaterial>,
+    spike: Handle<ColorMaterial>,
+    portal: Handle<ColorMaterial>,
+    obstacle: Handle<ColorMaterial>,
+}
+
+pub fn setup_hazard_assets(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands.insert_resource(HazardAssets {
+        patrol: materials.add(Color::srgb(2.0, 0.7, 0.1)),
+        spike: materials.add(Color::srgb(1.6, 1.6, 1.6)),
+        portal: materials.add(Color::srgb(0.2, 1.4, 2.0)),
+        obstacle: materials.add(Color::srgb(0.5, 0.5, 0.6)),
+    });
+}
+
+fn spawn_hazard(
+    commands: &mut Commands,
+    snake_assets: &SnakeAssets,
+    name: &'static str,
+    at: IVec2,
+    material: &Handle<ColorMaterial>,
+) -> Entity {
+    let pos = cell_to_world(at);
+    commands
+        .spawn((
+            Name::new(name),
+            CleanupOnRestart,
+            GridPosition(at),
+            Transform::from_xyz(pos.x, pos.y, 0.),
+            Mesh2d(snake_assets.straight.clone()),
+            MeshMaterial2d(material.clone()),
+        ))
+        .id()
+}
+
+/// Places the selected level's hazards in the arena. A level that hasn't
+/// loaded yet is left empty.
+pub fn spawn_level(
+    mut commands: Commands,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    mut occupancy: ResMut<Occupancy>,
+    snake_assets: Res<SnakeAssets>,
+    hazard_assets: Res<HazardAssets>,
+) {
+    let Some(level) = level_assets.get(levels.selected()) else {
+        return;
+    };
+    for corners in &level.patrols {
+        let corners: Vec<IVec2> = corners.iter().copied().map(cell).collect();
+        let patrol = Patrol::new(&corners);
+        if patrol.path.is_empty() || !patrol.path.iter().all(|&at| occupancy.in_bounds(at)) {
+            continue;
+        }
+        let at = patrol.cell();
+        let entity = spawn_hazard(
+            &mut commands,
+            &snake_assets,
+            "patrol",
+            at,
+            &hazard_assets.patrol,
+        );
+        commands.entity(entity).insert(patrol);
+        occupancy.add_patrol(at);
+    }
+    for spec in &level.spikes {
+        let at = cell(spec.cell);
+        if !occupancy.in_bounds(at) {
+            continue;
+        }
+        let spike = Spike {
+            on: spec.on,
+            off: spec.off,
+            tick: spec.offset,
+        };
+        let entity = spawn_hazard(
+            &mut commands,
+            &snake_assets,
+            "spike",
+            at,
+            &hazard_assets.spike,
+        );
+        commands.entity(entity).insert((
+            Transform::from_translation(cell_to_world(at).extend(0.))
+                .with_scale(Vec3::splat(spike_scale(&spike))),
+            spike,
+        ));
+        occupancy.set_hazard(at, Some(entity));
+    }
+    for &(a, b) in &level.portals {
+        let (a, b) = (cell(a), cell(b));
+        if !occupancy.in_bounds(a) || !occupancy.in_bounds(b) || a == b {
+            continue;
+        }
+        for (at, exit) in [(a, b), (b, a)] {
+            let entity = spawn_hazard(
+                &mut commands,
+                &snake_assets,
+                "portal",
+                at,
+                &hazard_assets.portal,
+            );
+            commands.entity(entity).insert((
+                Portal,
+                // Round-ish, to tell them from walls.
+                Transform::from_translation(cell_to_world(at).extend(0.))
+                    .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4))
+                    .with_scale(Vec3::splat(0.8)),
+            ));
+            occupancy.set_portal(at, Some(exit));
+        }
+    }
+}
+
+fn spike_scale(spike: &Spike) -> f32 {
+    if spike.raised() {
+        1.
+    } else {
+        RETRACTED_SCALE
+    }
+}
+
+/// Walks every patrol a cell along its path each tick.
+pub fn move_patrols(
+    timer: Res<MoveTimer>,
+    mut occupancy: ResMut<Occupancy>,
+    mut patrols: Query<(&mut Patrol, &mut GridPosition, &mut Transform)>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    for (mut patrol, mut position, mut transform) in &mut patrols {
+        occupancy.remove_patrol(position.0);
+        patrol.step();
+        position.0 = patrol.cell();
+        occupancy.add_patrol(position.0);
+        let pos = cell_to_world(position.0);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+/// Raises and lowers spikes on their schedules.
+pub fn toggle_spikes(timer: Res<MoveTimer>, mut spikes: Query<(&mut Spike, &mut Transform)>) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    for (mut spike, mut transform) in &mut spikes {
+        spike.tick = spike.tick.wrapping_add(1);
+        transform.scale = Vec3::splat(spike_scale(&spike));
+    }
+}
+
+#[derive(Resource)]
+pub struct ObstacleTimer(Timer);
+
+impl Default for ObstacleTimer {
+    fn default() -> Self {
+        ObstacleTimer(Timer::from_seconds(
+            OBSTACLE_SPAWN_SECS,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+/// Drops an obstacle on a free cell now and then, away from the snake and
+/// off every patrol's path. They stay for the rest of the game.
+pub fn spawn_obstacles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<ObstacleTimer>,
+    mut rng: ResMut<GameRng>,
+    mut occupancy: ResMut<Occupancy>,
+    snake_assets: Res<SnakeAssets>,
+    hazard_assets: Res<HazardAssets>,
+    snakes: Query<&Segments, With<PlayerControlled>>,
+    patrols: Query<&Patrol>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    use rand::Rng;
+    let rng = &mut rng.obstacles;
+    let (min, max) = occupancy.bounds();
+    let cell = IVec2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
+    let crowded = snakes
+        .iter()
+        .any(|segments| (segments.head_cell() - cell).abs().max_element() <= OBSTACLE_CLEARANCE);
+    // A patrol would walk straight through it.
+    let patrolled = patrols.iter().any(|patrol| patrol.path.contains(&cell));
+    if crowded || patrolled || !occupancy.is_free(cell) {
+        return;
+    }
+    let entity = spawn_hazard(
+        &mut commands,
+        &snake_assets,
+        "obstacle",
+        cell,
+        &hazard_assets.obstacle,
+    );
+    occupancy.set_hazard(cell, Some(entity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patrols_walk_back_and_forth_or_round_a_loop() {
+        let mut line = Patrol::new(&[IVec2::new(0, 0), IVec2::new(2, 0), IVec2::new(2, 1)]);
+        let mut walked = vec![line.cell()];
+        for _ in 0..6 {
+            line.step();
+            walked.push(line.cell());
+        }
+        let [a, b, c, d] = [(0, 0), (1, 0), (2, 0), (2, 1)].map(|(x, y)| IVec2::new(x, y));
+        assert_eq!(walked, [a, b, c, d, c, b, a]);
+
+        let mut square = Patrol::new(&[a, IVec2::new(1, 0), IVec2::new(1, 1), IVec2::new(0, 1), a]);
+        let mut walked = vec![square.cell()];
+        for _ in 0..4 {
+            square.step();
+            walked.push(square.cell());
+        }
+        assert_eq!(walked[0], walked[4]);
+        assert_eq!(walked[1], IVec2::new(1, 0));
+    }
+
+    #[test]
+    fn spikes_follow_their_schedule() {
+        let mut spike = Spike {
+            on: 2,
+            off: 3,
+            tick: 0,
+        };
+        let raised: Vec<bool> = (0..10)
+            .map(|_| {
+                let raised = spike.raised();
+                spike.tick += 1;
+                raised
+            })
+            .collect();
+        assert_eq!(
+            raised,
+            [true, true, false, false, false, true, true, false, false, false]
+        );
+    }
+}
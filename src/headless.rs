@@ -0,0 +1,126 @@
+//! A windowless game for tests and benchmarks, where every update is one
+//! tick.
+use std::time::Duration;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*, time::TimeUpdateStrategy};
+
+use crate::level::{load_levels, Level};
+use crate::mode::GameMode;
+use crate::occupancy::Occupancy;
+use crate::options::GameOptions;
+use crate::ron_asset::RonLoader;
+use crate::score::{update_combo, Combo, Score};
+use crate::synth::synthesize_sounds;
+use crate::theme::{load_themes, Theme};
+use crate::{
+    animate_food, food_collision_check, grow, length_check, move_snake, place_segments,
+    reset_occupancy, setup_snake_assets, shape_segments, spawn_food, FoodCollisionEvent,
+    FoodSpawnTimer, GameOverEvent, GameRng, Length, MoveTimer, SnakeAssets, SnakeBundle,
+};
+
+/// A windowless app running the parts of the game loop that spawn
+/// segments and food, with one snake and every update being one tick.
+pub fn headless_game() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_asset::<AnimationClip>()
+        .init_asset::<AnimationGraph>()
+        .init_asset::<AudioSource>()
+        .init_asset::<Theme>()
+        .init_asset::<Level>()
+        .init_asset_loader::<RonLoader<Level>>()
+        .add_event::<FoodCollisionEvent>()
+        .add_event::<GameOverEvent>()
+        // Exactly as long as an update, where `MOVE_SECS` as an `f32` is a
+        // hair longer.
+        .insert_resource(MoveTimer(Timer::new(
+            Duration::from_millis(300),
+            TimerMode::Repeating,
+        )))
+        .insert_resource(FoodSpawnTimer(Timer::from_seconds(
+            1.0,
+            TimerMode::Repeating,
+        )))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            300,
+        )))
+        .init_resource::<GameOptions>()
+        .init_resource::<Score>()
+        .init_resource::<Combo>()
+        .init_resource::<GameMode>()
+        .init_resource::<GameRng>()
+        .add_systems(
+            Startup,
+            (
+                synthesize_sounds,
+                load_themes,
+                setup_snake_assets,
+                load_levels,
+                reset_occupancy,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                move_snake,
+                place_segments,
+                shape_segments,
+                spawn_food,
+                animate_food,
+                length_check,
+                food_collision_check,
+                update_combo,
+                grow,
+            )
+                .chain(),
+        );
+    // Let a whole tick through in one update.
+    app.world_mut()
+        .resource_mut::<Time<Virtual>>()
+        .set_max_delta(Duration::from_secs(1));
+    app.world_mut().spawn(Window::default());
+    app.update();
+    app.world_mut()
+        .run_system_once(
+            |mut commands: Commands,
+             snake_assets: Res<SnakeAssets>,
+             mut occupancy: ResMut<Occupancy>| {
+                let snake = SnakeBundle::new(&snake_assets, &mut commands);
+                occupancy.add_segment(IVec2::ZERO);
+                commands.spawn(snake);
+            },
+        )
+        .unwrap();
+    app
+}
+
+/// A [`headless_game`] where food never appears, so nothing but its starting
+/// length grows the snake.
+pub fn headless_game_without_food() -> App {
+    let mut app = headless_game();
+    app.insert_resource(FoodSpawnTimer(Timer::from_seconds(1e6, TimerMode::Once)));
+    app
+}
+
+pub fn asset_counts(app: &App) -> [usize; 4] {
+    let world = app.world();
+    [
+        world.resource::<Assets<Mesh>>().len(),
+        world.resource::<Assets<ColorMaterial>>().len(),
+        world.resource::<Assets<AnimationClip>>().len(),
+        world.resource::<Assets<AnimationGraph>>().len(),
+    ]
+}
+
+/// Plays a tick with the snake made a segment longer first, so that every
+/// tick spawns a segment.
+pub fn tick_growing(app: &mut App) {
+    let world = app.world_mut();
+    for mut len in world.query::<&mut Length>().iter_mut(world) {
+        len.0 += 1;
+    }
+    app.update();
+}
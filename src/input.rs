@@ -0,0 +1,266 @@
+//! Abstract actions and the keys and gamepad buttons bound to them.
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// How far a stick has to be pushed before it steers.
+const STICK_DEAD_ZONE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Confirm,
+    Back,
+}
+
+impl Action {
+    /// Every action, in the order the controls screen lists them.
+    pub const ALL: [Action; 7] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Pause,
+        Action::Confirm,
+        Action::Back,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+        }
+    }
+
+    /// The fixed gamepad button for this action.
+    fn gamepad_button(&self) -> GamepadButton {
+        match self {
+            Action::Up => GamepadButton::DPadUp,
+            Action::Down => GamepadButton::DPadDown,
+            Action::Left => GamepadButton::DPadLeft,
+            Action::Right => GamepadButton::DPadRight,
+            Action::Pause => GamepadButton::Start,
+            Action::Confirm => GamepadButton::South,
+            Action::Back => GamepadButton::East,
+        }
+    }
+
+    /// Whether both actions can be asked for at the same time and so must not
+    /// share a key. Pause is only read in game, where Confirm and Back never are.
+    fn overlaps(&self, other: &Action) -> bool {
+        use Action::*;
+        !matches!(
+            (self, other),
+            (Pause, Confirm | Back) | (Confirm | Back, Pause)
+        )
+    }
+}
+
+/// The keys bound to each action. Any of an action's keys triggers it.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputMap {
+    up: Vec<KeyCode>,
+    down: Vec<KeyCode>,
+    left: Vec<KeyCode>,
+    right: Vec<KeyCode>,
+    pause: Vec<KeyCode>,
+    confirm: Vec<KeyCode>,
+    back: Vec<KeyCode>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            up: vec![KeyCode::ArrowUp],
+            down: vec![KeyCode::ArrowDown],
+            left: vec![KeyCode::ArrowLeft],
+            right: vec![KeyCode::ArrowRight],
+            pause: vec![KeyCode::Escape],
+            confirm: vec![KeyCode::Enter, KeyCode::Space],
+            back: vec![KeyCode::Escape],
+        }
+    }
+}
+
+impl InputMap {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        match action {
+            Action::Up => &self.up,
+            Action::Down => &self.down,
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::Pause => &self.pause,
+            Action::Confirm => &self.confirm,
+            Action::Back => &self.back,
+        }
+    }
+
+    fn keys_mut(&mut self, action: Action) -> &mut Vec<KeyCode> {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Pause => &mut self.pause,
+            Action::Confirm => &mut self.confirm,
+            Action::Back => &mut self.back,
+        }
+    }
+
+    pub fn just_pressed(&self, action: Action, keys: &ButtonInput<KeyCode>) -> bool {
+        keys.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    /// Another action that `key` is already bound to and would clash with `action`.
+    pub fn conflict(&self, action: Action, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|other| *other != action && action.overlaps(other))
+            .find(|other| self.keys(*other).contains(&key))
+    }
+
+    /// Makes `key` the only key for `action`, unless it clashes with another
+    /// action, which is returned instead.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), Action> {
+        if let Some(other) = self.conflict(action, key) {
+            return Err(other);
+        }
+        *self.keys_mut(action) = vec![key];
+        Ok(())
+    }
+
+    /// The keys for `action` as shown to the player.
+    pub fn describe(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .keys(action)
+            .iter()
+            .map(|key| format!("{key:?}"))
+            .collect();
+        if keys.is_empty() {
+            "Unbound".into()
+        } else {
+            keys.join(" / ")
+        }
+    }
+}
+
+/// Reads actions from the keyboard and every connected gamepad.
+#[derive(SystemParam)]
+pub struct Actions<'w, 's> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    input: Res<'w, InputMap>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl Actions<'_, '_> {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.key_just_pressed(action)
+            || self
+                .gamepads
+                .iter()
+                .any(|gamepad| gamepad.just_pressed(action.gamepad_button()))
+    }
+
+    /// Like [`Actions::just_pressed`], ignoring gamepads.
+    pub fn key_just_pressed(&self, action: Action) -> bool {
+        self.input.just_pressed(action, &self.keys)
+    }
+
+    /// The way one gamepad's D-pad, or failing that its left stick, is pushed.
+    pub fn steering(&self, gamepad: Entity) -> Option<Action> {
+        let gamepad = self.gamepads.get(gamepad).ok()?;
+        let dpad = gamepad.dpad();
+        let stick = if dpad != Vec2::ZERO {
+            dpad
+        } else {
+            gamepad.left_stick()
+        };
+        snap(stick, STICK_DEAD_ZONE)
+    }
+
+    pub fn is_connected(&self, gamepad: Entity) -> bool {
+        self.gamepads.contains(gamepad)
+    }
+}
+
+/// Snaps a stick, or anything else pushed in some direction, to the nearest
+/// of the four steering actions. Nothing shorter than `dead_zone` counts.
+pub fn snap(stick: Vec2, dead_zone: f32) -> Option<Action> {
+    if stick.length() < dead_zone {
+        None
+    } else if stick.x.abs() > stick.y.abs() {
+        Some(if stick.x > 0. {
+            Action::Right
+        } else {
+            Action::Left
+        })
+    } else if stick.y > 0. {
+        Some(Action::Up)
+    } else {
+        Some(Action::Down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let map = InputMap::default();
+        for action in Action::ALL {
+            for &key in map.keys(action) {
+                assert_eq!(map.conflict(action, key), None, "{action:?} {key:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bind_rejects_keys_used_by_overlapping_actions() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind(Action::Up, KeyCode::ArrowLeft), Err(Action::Left));
+        assert_eq!(map.keys(Action::Up), [KeyCode::ArrowUp]);
+
+        // Pause and Back are never read at the same time.
+        map.bind(Action::Back, KeyCode::Backspace).unwrap();
+        assert_eq!(map.bind(Action::Pause, KeyCode::Backspace), Ok(()));
+        assert_eq!(map.keys(Action::Pause), [KeyCode::Backspace]);
+
+        map.bind(Action::Up, KeyCode::KeyW).unwrap();
+        assert_eq!(map.keys(Action::Up), [KeyCode::KeyW]);
+    }
+
+    #[test]
+    fn stick_snaps_to_four_directions_outside_dead_zone() {
+        assert_eq!(snap(Vec2::new(0.3, -0.2), STICK_DEAD_ZONE), None);
+        assert_eq!(
+            snap(Vec2::new(0.9, 0.4), STICK_DEAD_ZONE),
+            Some(Action::Right)
+        );
+        assert_eq!(
+            snap(Vec2::new(-0.6, 0.1), STICK_DEAD_ZONE),
+            Some(Action::Left)
+        );
+        assert_eq!(snap(Vec2::new(0.4, 0.7), STICK_DEAD_ZONE), Some(Action::Up));
+        assert_eq!(
+            snap(Vec2::new(-0.5, -0.6), STICK_DEAD_ZONE),
+            Some(Action::Down)
+        );
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let map: InputMap = ron::from_str("(up: [KeyW])").unwrap();
+        assert_eq!(map.keys(Action::Up), [KeyCode::KeyW]);
+        assert_eq!(map.keys(Action::Down), [KeyCode::ArrowDown]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Levels: the hazards placed in the arena, loaded from `.level.ron` assets.
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ron_asset::{AssetList, RonAsset};
+
+/// Levels that ship with the game. The first is the open arena the game
+/// starts on.
+const BUILTIN_LEVELS: [&str; 4] = [
+    "levels/open.level.ron",
+    "levels/gauntlet.level.ron",
+    "levels/wormholes.level.ron",
+    "levels/expanse.level.ron",
+];
+
+/// Cells are `(x, y)` grid coordinates with the origin, where the snake
+/// starts, in the middle of the arena. Anything placed outside the arena is
+/// left out.
+#[derive(Asset, TypePath, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Level {
+    pub name: String,
+    /// How many cells the arena spans across and up. It can be bigger than
+    /// the window, which then scrolls to follow the snake. Without one the
+    /// arena fills the window.
+    pub arena: Option<[i32; 2]>,
+    /// Blocks that walk back and forth along a path, one cell per tick. Each
+    /// path is a list of corners joined by straight lines.
+    pub patrols: Vec<Vec<[i32; 2]>>,
+    pub spikes: Vec<SpikeSpec>,
+    /// Pairs of cells that lead to each other.
+    pub portals: Vec<([i32; 2], [i32; 2])>,
+}
+
+/// A cell that is deadly for `on` ticks, then safe for `off` ticks, over and
+/// over. `offset` ticks are skipped at the start, so spikes can take turns.
+#[derive(Deserialize, Clone)]
+pub struct SpikeSpec {
+    pub cell: [i32; 2],
+    pub on: u32,
+    pub off: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+pub fn cell([x, y]: [i32; 2]) -> IVec2 {
+    IVec2::new(x, y)
+}
+
+impl RonAsset for Level {
+    const EXTENSIONS: &'static [&'static str] = &["level.ron"];
+}
+
+pub type Levels = AssetList<Level>;
+
+pub fn load_levels(mut commands: Commands, server: Res<AssetServer>) {
+    commands.insert_resource(Levels::load(&server, &BUILTIN_LEVELS));
+}
+
+/// The name of the selected level, once it has loaded.
+pub fn level_name<'a>(levels: &Levels, assets: &'a Assets<Level>) -> &'a str {
+    assets
+        .get(levels.selected())
+        .map_or("...", |level| level.name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ron_asset::parse;
+
+    #[test]
+    fn builtin_levels_parse() {
+        for path in BUILTIN_LEVELS {
+            let bytes = std::fs::read(format!("assets/{path}")).unwrap();
+            if let Err(err) = parse::<Level>(&bytes) {
+                panic!("{path}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,1988 @@
+//! Snake, played in a window. Everything but [`run`] is private to the game,
+//! bar the pieces its benchmarks need, which the `bench` feature opens up.
+#![allow(clippy::type_complexity)]
+#![allow(clippy::too_many_arguments)]
+use bevy::app::AppExit;
+use bevy::{
+    animation::{animated_field, AnimationTarget, AnimationTargetId},
+    asset::RenderAssetUsages,
+    audio::{AudioPlugin, SpatialScale},
+    core_pipeline::{bloom::Bloom, tonemapping::Tonemapping},
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::VecDeque;
+
+mod camera;
+mod config;
+mod controls;
+mod daily;
+mod focus;
+mod hazard;
+#[cfg(any(test, feature = "bench"))]
+#[doc(hidden)]
+pub mod headless;
+mod input;
+mod level;
+mod menu;
+mod mode;
+mod music;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod occupancy;
+#[cfg(not(feature = "bench"))]
+mod occupancy;
+mod options;
+mod pointer;
+mod powerup;
+mod ron_asset;
+mod score;
+mod synth;
+mod theme;
+
+use camera::{
+    follow_snake, reset_camera_focus, setup_minimap, update_minimap, CameraFocus, Minimap,
+};
+use config::{load_config, save_config};
+use controls::{
+    capture_binding, controls_actions, rebinding_idle, refresh_binding_labels, setup_controls,
+};
+use daily::{
+    daily_actions, daily_label, end_daily, load_daily_records, record_daily, save_daily_records,
+    DailyAttempt, DailyRecords, DAILY_ARENA,
+};
+use focus::{highlight_focus, navigate_focus, MenuFocus};
+use hazard::{
+    move_patrols, setup_hazard_assets, spawn_level, spawn_obstacles, toggle_spikes, ObstacleTimer,
+    Spike,
+};
+use input::{Action, Actions};
+use level::{level_name, load_levels, Level, Levels};
+use menu::{
+    menu_interaction, relabel, restyle_menus, MenuAction, MenuItemLabel, MenuScreen, MenuSpec,
+};
+use mode::{run_clock, setup_countdown, GameMode};
+use music::{fade_music, switch_music, synthesize_music};
+use occupancy::Occupancy;
+use options::{
+    apply_options, options_actions, positional_sound_effect, refresh_option_labels, setup_options,
+    sound_effect, GameOptions, OptionsReturn, SegmentStyle, AUDIO_SCALE, EAR_GAP,
+};
+use pointer::pointer_direction;
+use powerup::{
+    apply_slow_motion, collect_power_ups, expire_power_ups, pull_food, setup_effect_hud,
+    setup_power_up_assets, spawn_power_ups, tick_effect, update_effect_hud, Ghost, Magnet,
+    PowerUpSpawnTimer, Shield, SlowMotion,
+};
+use ron_asset::RonLoader;
+use score::{reset_score, setup_hud, update_combo, update_hud, Combo};
+use synth::synthesize_sounds;
+use theme::{apply_theme, load_themes, CurrentTheme, Theme, Themes};
+
+/// Opens the window and plays until it is closed.
+pub fn run() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(AudioPlugin {
+            default_spatial_scale: SpatialScale::new_2d(AUDIO_SCALE),
+            ..default()
+        }))
+        .init_asset::<Theme>()
+        .init_asset_loader::<RonLoader<Theme>>()
+        .init_asset::<Level>()
+        .init_asset_loader::<RonLoader<Level>>()
+        .add_event::<FoodCollisionEvent>()
+        .add_event::<GameOverEvent>()
+        .add_event::<MenuAction>()
+        .insert_resource(MoveTimer(Timer::from_seconds(
+            MOVE_SECS,
+            TimerMode::Repeating,
+        )))
+        .insert_resource(FoodSpawnTimer(Timer::from_seconds(
+            1.0,
+            TimerMode::Repeating,
+        )))
+        .init_resource::<GameRng>()
+        .init_resource::<PowerUpSpawnTimer>()
+        .init_resource::<ObstacleTimer>()
+        .init_resource::<GameMode>()
+        .init_resource::<MenuFocus>()
+        .init_state::<GameState>()
+        .add_systems(
+            Startup,
+            (
+                setup_camera,
+                setup_minimap,
+                load_config,
+                load_daily_records,
+                synthesize_music,
+                setup_power_up_assets,
+                setup_hazard_assets,
+                load_levels,
+                (synthesize_sounds, load_themes, setup_snake_assets).chain(),
+            ),
+        )
+        // Menu focus, for the keyboard and gamepads
+        .add_systems(
+            PreUpdate,
+            navigate_focus
+                .after(bevy::ui::UiSystem::Focus)
+                .run_if(rebinding_idle),
+        )
+        .add_systems(PostUpdate, highlight_focus)
+        .add_systems(
+            Update,
+            (
+                (
+                    menu_interaction,
+                    (
+                        menu_actions,
+                        daily_actions.run_if(in_state(GameState::Menu)),
+                    ),
+                )
+                    .chain(),
+                relabel_theme_button,
+                relabel_level_button,
+                relabel_mode_button,
+                restyle_menus,
+            ),
+        )
+        // Main menu, clearing away any game left behind it
+        .add_systems(
+            OnEnter(GameState::Menu),
+            (cleanup_system::<CleanupOnRestart>, end_daily, setup_menu),
+        )
+        .add_systems(OnExit(GameState::Menu), cleanup_system::<MenuScreen>)
+        // Key bindings
+        .add_systems(OnEnter(GameState::Controls), setup_controls)
+        .add_systems(
+            Update,
+            (controls_actions, capture_binding, refresh_binding_labels)
+                .chain()
+                .after(menu_interaction)
+                .run_if(in_state(GameState::Controls)),
+        )
+        .add_systems(OnExit(GameState::Controls), cleanup_system::<MenuScreen>)
+        // Options, from the main menu or the pause menu
+        .add_systems(OnEnter(GameState::Options), setup_options)
+        .add_systems(
+            Update,
+            (options_actions, refresh_option_labels)
+                .chain()
+                .after(menu_interaction)
+                .run_if(in_state(GameState::Options)),
+        )
+        .add_systems(OnExit(GameState::Options), cleanup_system::<MenuScreen>)
+        // Clean slate
+        .add_systems(
+            OnEnter(GameState::StartGame),
+            (
+                cleanup_system::<CleanupOnRestart>,
+                reset_occupancy,
+                reset_camera_focus,
+                spawn_level,
+                reset_score,
+                reset_spawns,
+                setup_hud,
+                setup_effect_hud,
+                setup_countdown,
+                add_snake,
+            )
+                .chain(),
+        )
+        // Main game play loop
+        .add_systems(
+            Update,
+            (
+                (assign_gamepads, input_direction, pointer_direction).chain(),
+                input_pause,
+                apply_slow_motion,
+                move_snake,
+                place_segments,
+                shape_segments,
+                spawn_food,
+                animate_food,
+                (spawn_power_ups, collect_power_ups, pull_food).chain(),
+                (
+                    move_patrols,
+                    toggle_spikes,
+                    spawn_obstacles.run_if(resource_equals(GameMode::Survival)),
+                )
+                    .chain(),
+                (
+                    wall_collision_check,
+                    self_collision_check,
+                    hazard_collision_check,
+                    length_check,
+                    run_clock.run_if(resource_equals(GameMode::TimeAttack)),
+                )
+                    .chain(),
+                food_collision_check,
+                game_over_check,
+                update_combo,
+                grow,
+                update_hud,
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (
+                tick_effect::<Ghost>,
+                tick_effect::<Shield>,
+                tick_effect::<SlowMotion>,
+                tick_effect::<Magnet>,
+                expire_power_ups,
+                update_effect_hud,
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        // Paused
+        .add_systems(OnEnter(GameState::Pause), setup_pause)
+        .add_systems(Update, paused.run_if(in_state(GameState::Pause)))
+        .add_systems(OnExit(GameState::Pause), cleanup_system::<MenuScreen>)
+        // Dying
+        .add_systems(OnEnter(GameState::Dying), start_death_animation)
+        .add_systems(Update, dying.run_if(in_state(GameState::Dying)))
+        .add_systems(
+            Update,
+            (
+                (follow_snake, screen_shake).chain(),
+                update_minimap,
+                apply_theme,
+                apply_options,
+                save_config,
+                save_daily_records,
+            ),
+        )
+        // Music for whichever screen is showing
+        .add_systems(Update, (switch_music, fade_music).chain())
+        // Game Over
+        .add_systems(
+            OnEnter(GameState::GameOver),
+            (setup_game_over, record_daily),
+        )
+        .add_systems(
+            Update,
+            reveal_game_over.run_if(in_state(GameState::GameOver)),
+        )
+        .add_systems(
+            OnExit(GameState::GameOver),
+            (cleanup_game_over, cleanup_system::<MenuScreen>),
+        )
+        .run();
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+enum GameState {
+    #[default]
+    Menu,
+    Controls,
+    Options,
+    StartGame,
+    InGame,
+    Pause,
+    Dying,
+    GameOver,
+}
+
+#[derive(Resource)]
+struct GameOverData {
+    menu: Entity,
+    reveal: Timer,
+}
+
+#[derive(Event)]
+struct FoodCollisionEvent {
+    cell: IVec2,
+    kind: FoodKind,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum DeathCause {
+    Wall,
+    SelfCollision,
+    Starved,
+    Hazard,
+    TimeUp,
+}
+
+impl DeathCause {
+    fn message(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "You hit the wall",
+            DeathCause::SelfCollision => "You bit yourself",
+            DeathCause::Starved => "You shrank away to nothing",
+            DeathCause::Hazard => "You ran into a hazard",
+            DeathCause::TimeUp => "Time's up",
+        }
+    }
+}
+
+#[derive(Event, Clone, Copy)]
+struct GameOverEvent {
+    cause: DeathCause,
+    snake: Entity,
+    cell: IVec2,
+}
+
+/// The death that ended the last game, kept around for the game over screen.
+#[derive(Resource)]
+struct LastDeath(GameOverEvent);
+
+/// How many segments the snake should have. When this is above the number it
+/// has the snake grows by a segment per tick, and when below it sheds one.
+#[derive(Component)]
+struct Length(i32);
+
+/// Fewest segments shrinking leaves a snake with. Only a `Length` of zero or
+/// less ends the game.
+const MIN_LENGTH: i32 = 2;
+
+#[derive(Component, Eq, PartialEq)]
+enum Direction {
+    North,
+    East,
+    West,
+    South,
+}
+
+#[derive(Component, Default)]
+struct PlayerControlled;
+
+impl Direction {
+    fn to_x(&self) -> f32 {
+        match self {
+            Direction::North => 0.,
+            Direction::East => 1.,
+            Direction::West => -1.,
+            Direction::South => 0.,
+        }
+    }
+    fn to_y(&self) -> f32 {
+        match self {
+            Direction::North => 1.,
+            Direction::East => 0.,
+            Direction::West => 0.,
+            Direction::South => -1.,
+        }
+    }
+    fn to_offset(&self) -> IVec2 {
+        IVec2::new(self.to_x() as i32, self.to_y() as i32)
+    }
+
+    /// Heads the way a steering action points, unless that would turn the
+    /// snake back on itself.
+    fn turn(&mut self, action: Action) {
+        let (wanted, opposite) = match action {
+            Action::Left => (Direction::West, Direction::East),
+            Action::Right => (Direction::East, Direction::West),
+            Action::Up => (Direction::North, Direction::South),
+            Action::Down => (Direction::South, Direction::North),
+            _ => return,
+        };
+        if *self != opposite {
+            *self = wanted;
+        }
+    }
+}
+
+#[derive(Component)]
+struct Food;
+
+/// What kind of food it is. Bonus food is rarer, chimes when eaten and
+/// flickers through its colors faster, but is worth the same as any other.
+#[derive(Component, Debug, Clone, Copy, Eq, PartialEq)]
+enum FoodKind {
+    Regular,
+    Bonus,
+}
+
+/// One in this many pieces of food is a bonus.
+const BONUS_FOOD_ODDS: u32 = 8;
+
+/// How many segments eating food adds.
+const FOOD_GROWTH: i32 = 10;
+/// Points for eating food, before the combo multiplier.
+const FOOD_POINTS: u32 = 10;
+
+impl FoodKind {
+    fn hue_speed(self) -> f32 {
+        match self {
+            FoodKind::Regular => FOOD_HUE_SPEED,
+            FoodKind::Bonus => FOOD_HUE_SPEED * 3.,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct MoveTimer(Timer);
+
+/// Seconds between moves at normal speed.
+const MOVE_SECS: f32 = 0.3;
+
+#[derive(Resource)]
+struct FoodSpawnTimer(Timer);
+
+/// Picks where food, power-ups and obstacles appear. Each has a generator of
+/// its own, so one drawing more often never shifts what the others draw.
+/// Seeded for the daily challenge, so everyone playing it that day gets the
+/// same game.
+#[derive(Resource)]
+struct GameRng {
+    food: StdRng,
+    power_ups: StdRng,
+    obstacles: StdRng,
+}
+
+impl GameRng {
+    fn new(seed: Option<u64>) -> Self {
+        let rng = |stream: u64| {
+            seed.map_or_else(StdRng::from_entropy, |seed| {
+                StdRng::seed_from_u64(seed.wrapping_add(stream))
+            })
+        };
+        GameRng {
+            food: rng(0),
+            power_ups: rng(1),
+            obstacles: rng(2),
+        }
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::new(None)
+    }
+}
+
+/// The cell food, power-ups and hazards occupy. All game logic works on
+/// this; `Transform` is only for rendering. Snake segments have their cells
+/// kept in [`Segments`] instead.
+#[derive(Component, Clone, Copy, Eq, PartialEq)]
+struct GridPosition(IVec2);
+
+/// The cell a segment is drawn moving away from during the current tick.
+#[derive(Component, Clone, Copy)]
+struct PreviousGridPosition(IVec2);
+
+#[derive(Bundle)]
+struct Segment {
+    mesh: Mesh2d,
+    material: MeshMaterial2d<ColorMaterial>,
+    transform: Transform,
+    previous: PreviousGridPosition,
+}
+
+const SEGMENT_SIZE: f32 = 10.0;
+
+const GAME_OVER_REVEAL_SECS: f32 = 1.0;
+const DEATH_STAGGER_SECS: f32 = 0.05;
+const DEATH_BURST_SECS: f32 = 0.4;
+const DEATH_MAX_STAGGER_SECS: f32 = 1.5;
+const SCREEN_SHAKE_SECS: f32 = 0.5;
+const SCREEN_SHAKE_INTENSITY: f32 = 8.0;
+
+/// World-space center of a grid cell.
+fn cell_to_world(cell: IVec2) -> Vec2 {
+    cell.as_vec2() * SEGMENT_SIZE
+}
+
+impl Segment {
+    /// A segment drawn sliding out of `previous` into its cell.
+    fn new(snake_assets: &SnakeAssets, previous: IVec2) -> Self {
+        let pos = cell_to_world(previous);
+        Segment {
+            transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+            mesh: Mesh2d(snake_assets.straight.clone()),
+            material: MeshMaterial2d(snake_assets.snake_material.clone()),
+            previous: PreviousGridPosition(previous),
+        }
+    }
+}
+
+/// A snake's body from tail to head, with the cell each segment is in. There
+/// is always at least one segment and the back is always the head. Segments
+/// next to each other along the body are usually in neighbouring cells, but
+/// are apart where the snake went through a portal or round the edge of the
+/// arena; [`Occupancy::step_between`] tells which way it went.
+#[derive(Component)]
+struct Segments(VecDeque<(Entity, IVec2)>);
+
+impl Segments {
+    fn new(head: Entity, cell: IVec2) -> Self {
+        Segments(VecDeque::from([(head, cell)]))
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn head(&self) -> Entity {
+        self.0.back().unwrap().0
+    }
+
+    fn head_cell(&self) -> IVec2 {
+        self.0.back().unwrap().1
+    }
+
+    fn tail(&self) -> Entity {
+        self.0.front().unwrap().0
+    }
+
+    /// Every segment, from tail to head.
+    fn iter_body(&self) -> impl DoubleEndedIterator<Item = Entity> + ExactSizeIterator + '_ {
+        self.0.iter().map(|&(segment, _)| segment)
+    }
+
+    /// The cell of every segment, from tail to head.
+    fn cells(&self) -> impl DoubleEndedIterator<Item = IVec2> + ExactSizeIterator + '_ {
+        self.0.iter().map(|&(_, cell)| cell)
+    }
+
+    /// Adds `segment` in `cell` as the new head.
+    fn grow(&mut self, segment: Entity, cell: IVec2) {
+        self.0.push_back((segment, cell));
+    }
+
+    /// Removes the tail and returns it with the cell it was in. The head is
+    /// never removed.
+    fn shrink(&mut self) -> Option<(Entity, IVec2)> {
+        if self.0.len() > 1 {
+            self.0.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Moves the tail into `cell` as the new head, returning it and the cell
+    /// it left.
+    fn advance(&mut self, cell: IVec2) -> (Entity, IVec2) {
+        match self.shrink() {
+            Some((segment, from)) => {
+                self.grow(segment, cell);
+                (segment, from)
+            }
+            None => {
+                let head = self.0.back_mut().unwrap();
+                let from = head.1;
+                head.1 = cell;
+                (head.0, from)
+            }
+        }
+    }
+}
+
+#[derive(Bundle)]
+struct SnakeBundle {
+    desired_len: Length,
+    segments: Segments,
+    dir: Direction,
+    player: PlayerControlled,
+}
+
+#[derive(Component)]
+struct CleanupOnRestart;
+
+fn cleanup_system<T: Component>(mut commands: Commands, q: Query<Entity, With<T>>) {
+    for e in &q {
+        commands.entity(e).despawn();
+    }
+}
+
+impl SnakeBundle {
+    fn new(snake_assets: &SnakeAssets, commands: &mut Commands) -> Self {
+        let segment = commands
+            .spawn((
+                Name::new("segment"),
+                CleanupOnRestart,
+                Segment::new(snake_assets, IVec2::ZERO),
+            ))
+            .id();
+        SnakeBundle {
+            desired_len: Length(10),
+            segments: Segments::new(segment, IVec2::ZERO),
+            dir: Direction::North,
+            player: PlayerControlled,
+        }
+    }
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    theme: Res<CurrentTheme>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    mode: Res<GameMode>,
+    records: Res<DailyRecords>,
+) {
+    MenuSpec::new(None)
+        .item("Start", MenuAction::Start)
+        .item(daily_label(&records), MenuAction::DailyChallenge)
+        .item(mode_label(*mode), MenuAction::CycleMode)
+        .item(level_label(&levels, &level_assets), MenuAction::CycleLevel)
+        .item("Controls", MenuAction::Controls)
+        .item("Options", MenuAction::Options)
+        .item(theme_label(&theme), MenuAction::CycleTheme)
+        .spawn(&mut commands, &theme);
+}
+
+fn level_label(levels: &Levels, level_assets: &Assets<Level>) -> String {
+    format!("Level: {}", level_name(levels, level_assets))
+}
+
+/// Keeps the level button's label up to date as levels are picked and load.
+fn relabel_level_button(
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    mut events: EventReader<AssetEvent<Level>>,
+    mut labels: Query<(&mut Text, &MenuItemLabel)>,
+) {
+    if events.read().count() > 0 || levels.is_changed() {
+        let label = level_label(&levels, &level_assets);
+        relabel(&mut labels, MenuAction::CycleLevel, &label);
+    }
+}
+
+fn mode_label(mode: GameMode) -> String {
+    format!("Mode: {}", mode.label())
+}
+
+fn relabel_mode_button(mode: Res<GameMode>, mut labels: Query<(&mut Text, &MenuItemLabel)>) {
+    if mode.is_changed() {
+        relabel(&mut labels, MenuAction::CycleMode, &mode_label(*mode));
+    }
+}
+
+fn theme_label(theme: &CurrentTheme) -> String {
+    format!("Theme: {}", theme.name)
+}
+
+fn relabel_theme_button(theme: Res<CurrentTheme>, mut labels: Query<(&mut Text, &MenuItemLabel)>) {
+    if theme.is_changed() {
+        relabel(&mut labels, MenuAction::CycleTheme, &theme_label(&theme));
+    }
+}
+
+/// Handles the buttons of every screen but the controls and options screens,
+/// which handle their own.
+fn menu_actions(
+    mut commands: Commands,
+    mut actions: EventReader<MenuAction>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut themes: ResMut<Themes>,
+    mut levels: ResMut<Levels>,
+    mut mode: ResMut<GameMode>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for action in actions.read() {
+        match action {
+            MenuAction::Start => next_state.set(GameState::StartGame),
+            MenuAction::Resume => next_state.set(GameState::InGame),
+            MenuAction::Quit => {
+                exit.write(AppExit::Success);
+            }
+            MenuAction::MainMenu => next_state.set(GameState::Menu),
+            MenuAction::Controls => next_state.set(GameState::Controls),
+            MenuAction::Options => {
+                commands.insert_resource(OptionsReturn(*state.get()));
+                next_state.set(GameState::Options);
+            }
+            MenuAction::CycleTheme => themes.cycle(),
+            MenuAction::CycleLevel => levels.cycle(),
+            MenuAction::CycleMode => mode.cycle(),
+            MenuAction::Back
+            | MenuAction::DailyChallenge
+            | MenuAction::Rebind(_)
+            | MenuAction::TogglePointerSteering
+            | MenuAction::Adjust(_)
+            | MenuAction::AdjustDown(_) => {}
+        }
+    }
+}
+
+#[derive(Component)]
+struct CollisionHighlight;
+
+fn setup_game_over(
+    mut commands: Commands,
+    snake_assets: Res<SnakeAssets>,
+    last_death: Option<Res<LastDeath>>,
+    theme: Res<CurrentTheme>,
+) {
+    let message = last_death
+        .as_ref()
+        .map_or("Game Over", |d| d.0.cause.message());
+    if let Some(death) = &last_death {
+        let pos = cell_to_world(death.0.cell);
+        commands.spawn((
+            Name::new("collision highlight"),
+            CollisionHighlight,
+            CleanupOnRestart,
+            Transform::from_xyz(pos.x, pos.y, 1.0),
+            Mesh2d(snake_assets.straight.clone()),
+            MeshMaterial2d(snake_assets.highlight_material.clone()),
+        ));
+    }
+    let menu = MenuSpec::new(Some(message))
+        .item("Retry", MenuAction::Start)
+        .item("Main Menu", MenuAction::MainMenu)
+        .item("Quit", MenuAction::Quit)
+        .spawn(&mut commands, &theme);
+    // Stays hidden while the collision cell is highlighted.
+    commands.entity(menu).insert(Visibility::Hidden);
+    commands.insert_resource(GameOverData {
+        menu,
+        reveal: Timer::from_seconds(GAME_OVER_REVEAL_SECS, TimerMode::Once),
+    });
+}
+
+fn reveal_game_over(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut game_over_data: ResMut<GameOverData>,
+    mut visibility: Query<&mut Visibility>,
+    highlight: Query<Entity, With<CollisionHighlight>>,
+) {
+    if game_over_data.reveal.tick(time.delta()).just_finished() {
+        if let Ok(mut menu) = visibility.get_mut(game_over_data.menu) {
+            *menu = Visibility::Inherited;
+        }
+        for e in &highlight {
+            commands.entity(e).despawn();
+        }
+    } else if !game_over_data.reveal.finished() {
+        // Blink the collision cell a few times a second.
+        let on = ((game_over_data.reveal.elapsed_secs() * 6.0) as u32).is_multiple_of(2);
+        for e in &highlight {
+            if let Ok(mut v) = visibility.get_mut(e) {
+                *v = if on {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            }
+        }
+    }
+}
+
+fn cleanup_game_over(mut commands: Commands, highlight: Query<Entity, With<CollisionHighlight>>) {
+    for e in &highlight {
+        commands.entity(e).despawn();
+    }
+}
+
+fn setup_pause(mut commands: Commands, theme: Res<CurrentTheme>) {
+    MenuSpec::new(Some("Paused"))
+        .item("Unpause", MenuAction::Resume)
+        .item("Options", MenuAction::Options)
+        .item("Main Menu", MenuAction::MainMenu)
+        .spawn(&mut commands, &theme);
+}
+
+fn paused(actions: Actions, mut next_state: ResMut<NextState<GameState>>) {
+    if [Action::Back, Action::Pause]
+        .into_iter()
+        .any(|action| actions.just_pressed(action))
+    {
+        next_state.set(GameState::InGame);
+    }
+}
+
+/// Sizes the arena for the selected level, or to fill the window when the
+/// level doesn't say and it isn't the daily challenge.
+fn reset_occupancy(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    levels: Res<Levels>,
+    level_assets: Res<Assets<Level>>,
+    daily: Option<Res<DailyAttempt>>,
+) {
+    // The daily challenge never fits the window, so everyone gets the same
+    // arena whatever their screen.
+    let arena = level_assets
+        .get(levels.selected())
+        .and_then(|level| level.arena)
+        .or(daily.map(|_| DAILY_ARENA));
+    let occupancy = match arena {
+        Some(size) => {
+            let size = level::cell(size).max(IVec2::ONE);
+            let min = -size / 2;
+            Occupancy::new(min, min + size - IVec2::ONE)
+        }
+        None => {
+            let half = windows.iter().next().map_or(IVec2::ZERO, |window| {
+                (Vec2::new(window.resolution.width(), window.resolution.height())
+                    / SEGMENT_SIZE
+                    / 2.)
+                    .floor()
+                    .as_ivec2()
+            });
+            Occupancy::new(-half, half)
+        }
+    };
+    commands.insert_resource(occupancy);
+}
+
+fn add_snake(
+    snake_assets: Res<SnakeAssets>,
+    mut occupancy: ResMut<Occupancy>,
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    start_sound: Res<StartSound>,
+    options: Res<GameOptions>,
+) {
+    let snake = SnakeBundle::new(&snake_assets, &mut commands);
+    occupancy.add_segment(IVec2::ZERO);
+    commands.spawn((Name::new("snake"), CleanupOnRestart, snake));
+    commands.spawn(sound_effect(&start_sound.0, &options));
+    next_state.set(GameState::InGame);
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera2d,
+        Camera {
+            hdr: true,
+            ..default()
+        },
+        Tonemapping::TonyMcMapface,
+        Bloom::default(),
+        SpatialListener::new(EAR_GAP),
+        CameraFocus::default(),
+        // The minimap camera draws later, which would otherwise make it the
+        // one menus are drawn on.
+        IsDefaultUiCamera,
+    ));
+}
+
+#[derive(Resource)]
+struct DyingTimer(Timer);
+
+#[derive(Component)]
+struct ScreenShake {
+    timer: Timer,
+    intensity: f32,
+}
+
+fn start_death_animation(
+    mut commands: Commands,
+    mut animations: ResMut<Assets<AnimationClip>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    last_death: Res<LastDeath>,
+    snakes: Query<&Segments>,
+    cameras: Query<Entity, (With<Camera2d>, Without<Minimap>)>,
+) {
+    for camera in &cameras {
+        commands.entity(camera).insert(ScreenShake {
+            timer: Timer::from_seconds(SCREEN_SHAKE_SECS, TimerMode::Once),
+            intensity: SCREEN_SHAKE_INTENSITY,
+        });
+    }
+    let snake = last_death.0.snake;
+    let Ok(segments) = snakes.get(snake) else {
+        commands.insert_resource(DyingTimer(Timer::from_seconds(0.0, TimerMode::Once)));
+        return;
+    };
+    // Long snakes pop faster so the whole thing never takes much more than a second or two.
+    let stagger = DEATH_STAGGER_SECS.min(DEATH_MAX_STAGGER_SECS / segments.len() as f32);
+    let mut animation = AnimationClip::default();
+    // Burst the segments one after another, starting from the head.
+    for (i, segment) in segments.iter_body().rev().enumerate() {
+        let target_id = AnimationTargetId::from_name(&Name::new(format!("dying segment {i}")));
+        let start = i as f32 * stagger;
+        let mut scale = vec![
+            (start, Vec3::ONE),
+            (start + DEATH_BURST_SECS * 0.3, Vec3::splat(1.8)),
+            (start + DEATH_BURST_SECS, Vec3::ZERO),
+        ];
+        let mut rotation = vec![
+            (start, Quat::IDENTITY),
+            (
+                start + DEATH_BURST_SECS,
+                Quat::from_rotation_z(std::f32::consts::PI),
+            ),
+        ];
+        if start > 0.0 {
+            scale.insert(0, (0.0, Vec3::ONE));
+            rotation.insert(0, (0.0, Quat::IDENTITY));
+        }
+        animation.add_curve_to_target(
+            target_id,
+            AnimatableCurve::new(
+                animated_field!(Transform::scale),
+                UnevenSampleAutoCurve::new(scale).unwrap(),
+            ),
+        );
+        animation.add_curve_to_target(
+            target_id,
+            AnimatableCurve::new(
+                animated_field!(Transform::rotation),
+                UnevenSampleAutoCurve::new(rotation).unwrap(),
+            ),
+        );
+        commands.entity(segment).insert(AnimationTarget {
+            id: target_id,
+            player: snake,
+        });
+    }
+    let (graph, animation_index) = AnimationGraph::from_clip(animations.add(animation));
+    let mut animation_player = AnimationPlayer::default();
+    animation_player.play(animation_index);
+    commands
+        .entity(snake)
+        .insert((AnimationGraphHandle(graphs.add(graph)), animation_player));
+
+    let duration = stagger * segments.len() as f32 + DEATH_BURST_SECS;
+    commands.insert_resource(DyingTimer(Timer::from_seconds(duration, TimerMode::Once)));
+}
+
+fn dying(
+    time: Res<Time>,
+    mut timer: ResMut<DyingTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if timer.0.tick(time.delta()).finished() {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn screen_shake(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut cameras: Query<(Entity, &mut Transform, &mut ScreenShake)>,
+) {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for (camera, mut transform, mut shake) in &mut cameras {
+        // Shakes around wherever the camera is following, which puts it
+        // back every frame.
+        if shake.timer.tick(time.delta()).finished() {
+            commands.entity(camera).remove::<ScreenShake>();
+        } else {
+            let strength = shake.intensity * shake.timer.fraction_remaining();
+            transform.translation.x += rng.gen_range(-1.0..=1.0) * strength;
+            transform.translation.y += rng.gen_range(-1.0..=1.0) * strength;
+        }
+    }
+}
+
+fn move_snake(
+    time: Res<Time>,
+    mut timer: ResMut<MoveTimer>,
+    snake_assets: Res<SnakeAssets>,
+    mode: Res<GameMode>,
+    mut query: Query<(Entity, &mut Segments, &Length, &Direction, Has<Shield>)>,
+    mut previous_query: Query<&mut PreviousGridPosition>,
+    mut occupancy: ResMut<Occupancy>,
+    mut commands: Commands,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        for (snake, mut segments, len, dir, shielded) in &mut query {
+            let old_head = segments.head_cell();
+            let mut new_head = occupancy.step(old_head, dir.to_offset());
+            // Leaving the arena comes back in at the opposite wall in Zen
+            // mode, or with a shield, which it uses up. Doing it here means
+            // nothing after ever sees the head outside.
+            let wrapped = !occupancy.in_bounds(new_head) && (!mode.deadly() || shielded);
+            if wrapped {
+                if mode.deadly() {
+                    commands.entity(snake).remove::<Shield>();
+                }
+                new_head = occupancy.wrap(new_head);
+            }
+            // A wrapped head appears at the far wall rather than slide in
+            // from outside the arena.
+            let head_from = if wrapped {
+                new_head
+            } else {
+                new_head - dir.to_offset()
+            };
+            occupancy.add_segment(new_head);
+            let target = len.0.max(MIN_LENGTH) as usize;
+            if segments.len() > target {
+                if let Some((tail, cell)) = segments.shrink() {
+                    occupancy.remove_segment(cell);
+                    commands.entity(tail).despawn();
+                }
+            }
+            if target <= segments.len() {
+                let (_, left) = segments.advance(new_head);
+                occupancy.remove_segment(left);
+                // Every segment is drawn sliding into the cell of the one in
+                // front of it, so the tail starts from where it just left.
+                // Across a portal it slides out of the far one instead.
+                let mut previous = left;
+                for (segment, cell) in segments.iter_body().zip(segments.cells()) {
+                    if let Ok(mut from) = previous_query.get_mut(segment) {
+                        from.0 = cell - occupancy.step_between(previous, cell);
+                    }
+                    previous = cell;
+                }
+                if let Ok(mut from) = previous_query.get_mut(segments.head()) {
+                    if wrapped {
+                        from.0 = head_from;
+                    }
+                }
+            } else {
+                // The rest of the body stays put while the snake grows.
+                for (segment, cell) in segments.iter_body().zip(segments.cells()) {
+                    if let Ok(mut from) = previous_query.get_mut(segment) {
+                        from.0 = cell;
+                    }
+                }
+                let segment = commands
+                    .spawn((
+                        Name::new("segment"),
+                        CleanupOnRestart,
+                        Segment::new(&snake_assets, head_from),
+                    ))
+                    .id();
+                segments.grow(segment, new_head);
+            }
+        }
+    }
+}
+
+/// Places segments on screen from their grid cells, gliding between the
+/// previous and current cell when smooth movement is turned on.
+fn place_segments(
+    timer: Res<MoveTimer>,
+    options: Res<GameOptions>,
+    snakes: Query<&Segments>,
+    mut segments: Query<(&PreviousGridPosition, &mut Transform)>,
+) {
+    let t = if options.smooth_movement {
+        timer.0.fraction()
+    } else {
+        1.0
+    };
+    for snake in &snakes {
+        for (segment, cell) in snake.iter_body().zip(snake.cells()) {
+            let Ok((previous, mut transform)) = segments.get_mut(segment) else {
+                continue;
+            };
+            let pos = cell_to_world(previous.0).lerp(cell_to_world(cell), t);
+            transform.translation.x = pos.x;
+            transform.translation.y = pos.y;
+        }
+    }
+}
+
+/// Number of steps around the hue wheel that food cycles through.
+const FOOD_HUES: usize = 72;
+/// How fast food cycles through hues, in degrees per second.
+const FOOD_HUE_SPEED: f32 = 100.0;
+
+/// Meshes, materials and animations shared by every segment and piece of
+/// food, so spawning them never creates new assets.
+#[derive(Resource)]
+struct SnakeAssets {
+    // Segment meshes are all built pointing North and rotated into place by
+    // [`shape_segments`].
+    straight: Handle<Mesh>,
+    head: Handle<Mesh>,
+    tail: Handle<Mesh>,
+    /// Joins a neighbour to the West with one to the North.
+    corner: Handle<Mesh>,
+    snake_material: Handle<ColorMaterial>,
+    /// The food color rotated in even steps around the hue wheel.
+    food_materials: Vec<Handle<ColorMaterial>>,
+    food_graph: Handle<AnimationGraph>,
+    food_animation: AnimationNodeIndex,
+    highlight_material: Handle<ColorMaterial>,
+}
+
+impl SnakeAssets {
+    /// Updates the shared materials in place to match `theme`.
+    fn recolor(&self, materials: &mut Assets<ColorMaterial>, theme: &CurrentTheme) {
+        if let Some(material) = materials.get_mut(&self.snake_material) {
+            material.color = theme.snake;
+        }
+        if let Some(material) = materials.get_mut(&self.highlight_material) {
+            material.color = theme.highlight;
+        }
+        let food: Hsla = theme.food.into();
+        for (i, handle) in self.food_materials.iter().enumerate() {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = food.rotate_hue(i as f32 * 360. / FOOD_HUES as f32).into();
+            }
+        }
+    }
+}
+
+/// Builds a flat mesh from the outline of a convex polygon given counter-clockwise.
+fn polygon_mesh(points: &[Vec2]) -> Mesh {
+    let positions: Vec<[f32; 3]> = points.iter().map(|p| [p.x, p.y, 0.]).collect();
+    let normals = vec![[0., 0., 1.]; points.len()];
+    let uvs: Vec<[f32; 2]> = points
+        .iter()
+        .map(|p| [p.x / SEGMENT_SIZE + 0.5, 0.5 - p.y / SEGMENT_SIZE])
+        .collect();
+    let indices = (1..points.len() as u32 - 1)
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn setup_snake_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut animations: ResMut<Assets<AnimationClip>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    theme: Res<CurrentTheme>,
+) {
+    let h = SEGMENT_SIZE / 2.;
+    let head = polygon_mesh(&[
+        Vec2::new(-h, -h),
+        Vec2::new(h, -h),
+        Vec2::new(h, 0.2 * h),
+        Vec2::new(0., h),
+        Vec2::new(-h, 0.2 * h),
+    ]);
+    let tail = polygon_mesh(&[
+        Vec2::new(-0.3 * h, -h),
+        Vec2::new(0.3 * h, -h),
+        Vec2::new(h, h),
+        Vec2::new(-h, h),
+    ]);
+    // A quarter disc around the cell's North-West corner, which rounds off
+    // the outside of the turn.
+    let corner = Mesh::from(CircularSector::new(
+        SEGMENT_SIZE,
+        std::f32::consts::FRAC_PI_4,
+    ))
+    .rotated_by(Quat::from_rotation_z(-3. * std::f32::consts::FRAC_PI_4))
+    .translated_by(Vec3::new(-h, h, 0.));
+
+    // Every piece of food is named "food", so one clip targets them all and
+    // each only needs its own player.
+    let mut animation = AnimationClip::default();
+    animation.add_curve_to_target(
+        AnimationTargetId::from_name(&Name::new("food")),
+        AnimatableCurve::new(
+            animated_field!(Transform::scale),
+            UnevenSampleAutoCurve::new([0.0, 1.0, 2.0].into_iter().zip([
+                Vec3::splat(0.5),
+                Vec3::splat(1.0),
+                Vec3::splat(0.5),
+            ]))
+            .unwrap(),
+        ),
+    );
+    let (graph, food_animation) = AnimationGraph::from_clip(animations.add(animation));
+
+    let mut new_material = || materials.add(ColorMaterial::default());
+    let snake_assets = SnakeAssets {
+        straight: meshes.add(Rectangle::new(SEGMENT_SIZE, SEGMENT_SIZE)),
+        head: meshes.add(head),
+        tail: meshes.add(tail),
+        corner: meshes.add(corner),
+        snake_material: new_material(),
+        food_materials: (0..FOOD_HUES).map(|_| new_material()).collect(),
+        food_graph: graphs.add(graph),
+        food_animation,
+        highlight_material: new_material(),
+    };
+    snake_assets.recolor(&mut materials, &theme);
+    commands.insert_resource(snake_assets);
+}
+
+/// Rotation that turns something pointing North to point along `offset`.
+fn rotation_towards(offset: IVec2) -> Quat {
+    Quat::from_rotation_z(offset.as_vec2().to_angle() - std::f32::consts::FRAC_PI_2)
+}
+
+/// Picks the mesh and rotation of every segment from its neighbours.
+fn shape_segments(
+    options: Res<GameOptions>,
+    snake_assets: Res<SnakeAssets>,
+    occupancy: Res<Occupancy>,
+    snakes: Query<(&Segments, &Direction)>,
+    mut shapes: Query<(&mut Mesh2d, &mut Transform)>,
+) {
+    for (segments, dir) in &snakes {
+        let cells: Vec<IVec2> = segments.cells().collect();
+        for (i, segment) in segments.iter_body().enumerate() {
+            let Ok((mut mesh, mut transform)) = shapes.get_mut(segment) else {
+                continue;
+            };
+            let (handle, rotation) = if options.segment_style == SegmentStyle::Flat {
+                (&snake_assets.straight, Quat::IDENTITY)
+            } else if segment == segments.head() {
+                (&snake_assets.head, rotation_towards(dir.to_offset()))
+            } else if segment == segments.tail() {
+                let ahead = occupancy.step_between(cells[0], cells[1]);
+                (&snake_assets.tail, rotation_towards(ahead))
+            } else {
+                let behind = -occupancy.step_between(cells[i - 1], cells[i]);
+                let ahead = occupancy.step_between(cells[i], cells[i + 1]);
+                if behind + ahead == IVec2::ZERO {
+                    (&snake_assets.straight, Quat::IDENTITY)
+                } else {
+                    // The corner mesh joins West and North, so rotate that
+                    // diagonal onto the one between the two neighbours.
+                    let diagonal = (behind + ahead).as_vec2();
+                    let angle = diagonal.to_angle() - Vec2::new(-1., 1.).to_angle();
+                    (&snake_assets.corner, Quat::from_rotation_z(angle))
+                }
+            };
+            if mesh.0 != *handle {
+                mesh.0 = handle.clone();
+            }
+            transform.rotation = rotation;
+        }
+    }
+}
+
+/// The gamepad steering a snake. The keyboard steers every snake.
+#[derive(Component)]
+struct AssignedGamepad(Entity);
+
+/// Hands each snake without a gamepad the next connected one nobody is using,
+/// and takes back gamepads that have been unplugged.
+fn assign_gamepads(
+    mut commands: Commands,
+    actions: Actions,
+    gamepads: Query<Entity, With<Gamepad>>,
+    snakes: Query<(Entity, Option<&AssignedGamepad>), With<PlayerControlled>>,
+) {
+    let mut taken: Vec<Entity> = snakes
+        .iter()
+        .filter_map(|(_, assigned)| assigned.map(|a| a.0))
+        .filter(|&gamepad| actions.is_connected(gamepad))
+        .collect();
+    for (snake, assigned) in &snakes {
+        if assigned.is_some_and(|a| actions.is_connected(a.0)) {
+            continue;
+        }
+        match gamepads.iter().find(|gamepad| !taken.contains(gamepad)) {
+            Some(gamepad) => {
+                taken.push(gamepad);
+                commands.entity(snake).insert(AssignedGamepad(gamepad));
+            }
+            None if assigned.is_some() => {
+                commands.entity(snake).remove::<AssignedGamepad>();
+            }
+            None => {}
+        }
+    }
+}
+
+fn input_direction(
+    actions: Actions,
+    mut direction: Query<(&mut Direction, Option<&AssignedGamepad>), With<PlayerControlled>>,
+) {
+    for (mut dir, gamepad) in &mut direction {
+        let turn = [Action::Left, Action::Right, Action::Up, Action::Down]
+            .into_iter()
+            .find(|&action| actions.key_just_pressed(action))
+            .or_else(|| gamepad.and_then(|g| actions.steering(g.0)));
+        if let Some(turn) = turn {
+            dir.turn(turn);
+        }
+    }
+}
+
+fn input_pause(actions: Actions, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.just_pressed(Action::Pause) {
+        next_state.set(GameState::Pause);
+    }
+}
+
+/// Starts what appears during a game over, from the daily challenge's seed
+/// if that is what is being played.
+fn reset_spawns(mut commands: Commands, daily: Option<Res<DailyAttempt>>) {
+    commands.insert_resource(GameRng::new(daily.map(|daily| daily.seed)));
+    commands.insert_resource(FoodSpawnTimer(Timer::from_seconds(
+        1.0,
+        TimerMode::Repeating,
+    )));
+    commands.insert_resource(PowerUpSpawnTimer::default());
+    commands.insert_resource(ObstacleTimer::default());
+}
+
+fn spawn_food(
+    time: Res<Time>,
+    mut timer: ResMut<FoodSpawnTimer>,
+    mut rng: ResMut<GameRng>,
+    mut commands: Commands,
+    snake_assets: Res<SnakeAssets>,
+    mut occupancy: ResMut<Occupancy>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        use rand::Rng;
+        let rng = &mut rng.food;
+        let (min, max) = occupancy.bounds();
+        // Everything is drawn every time, so the sequence doesn't depend on
+        // where the snake happens to be.
+        let cell = IVec2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
+        let kind = if rng.gen_ratio(1, BONUS_FOOD_ODDS) {
+            FoodKind::Bonus
+        } else {
+            FoodKind::Regular
+        };
+        // Don't place the food on top of the snek
+        if !occupancy.is_free(cell) {
+            return;
+        }
+
+        let pos = cell_to_world(cell);
+        let food = Name::new("food");
+        let food_animation_target_id = AnimationTargetId::from_name(&food);
+        let mut animation_player = AnimationPlayer::default();
+        animation_player.play(snake_assets.food_animation).repeat();
+
+        let food_id = commands
+            .spawn((
+                food,
+                Food,
+                kind,
+                CleanupOnRestart,
+                GridPosition(cell),
+                Transform::from_xyz(pos.x, pos.y, 0.0),
+                FoodHue(0.),
+                Mesh2d(snake_assets.straight.clone()),
+                MeshMaterial2d(snake_assets.food_materials[0].clone()),
+                AnimationGraphHandle(snake_assets.food_graph.clone()),
+                animation_player,
+            ))
+            .id();
+        commands.entity(food_id).insert(AnimationTarget {
+            id: food_animation_target_id,
+            player: food_id,
+        });
+        occupancy.set_food(cell, Some(food_id));
+    }
+}
+
+/// How far a piece of food has rotated around the hue wheel, in degrees.
+#[derive(Component)]
+struct FoodHue(f32);
+
+fn animate_food(
+    mut food: Query<(&mut FoodHue, &FoodKind, &mut MeshMaterial2d<ColorMaterial>)>,
+    time: Res<Time>,
+    snake_assets: Res<SnakeAssets>,
+) {
+    for (mut hue, kind, mut material) in &mut food {
+        hue.0 = (hue.0 + time.delta_secs() * kind.hue_speed()) % 360.;
+        let step = (hue.0 / 360. * FOOD_HUES as f32) as usize % FOOD_HUES;
+        let handle = &snake_assets.food_materials[step];
+        if material.0 != *handle {
+            material.0 = handle.clone();
+        }
+    }
+}
+
+fn food_collision_check(
+    mut commands: Commands,
+    mut food_collision_writer: EventWriter<FoodCollisionEvent>,
+    mut occupancy: ResMut<Occupancy>,
+    segments: Query<&Segments, With<PlayerControlled>>,
+    kinds: Query<&FoodKind>,
+) {
+    for segments in &segments {
+        let head = segments.head_cell();
+        if let Some(food) = occupancy.food_at(head) {
+            occupancy.set_food(head, None);
+            let kind = kinds.get(food).copied().unwrap_or(FoodKind::Regular);
+            commands.entity(food).despawn();
+            food_collision_writer.write(FoodCollisionEvent { cell: head, kind });
+        }
+    }
+}
+
+fn grow(
+    mut commands: Commands,
+    eat_sound: Res<EatSound>,
+    options: Res<GameOptions>,
+    combo: Res<Combo>,
+    mode: Res<GameMode>,
+    mut food_collision_reader: EventReader<FoodCollisionEvent>,
+    mut snake: Query<&mut Length, With<PlayerControlled>>,
+) {
+    if let Some(event) = food_collision_reader.read().next() {
+        commands.spawn(positional_sound_effect(
+            eat_sound.for_kind(event.kind),
+            &options,
+            cell_to_world(event.cell),
+            combo.pitch(),
+        ));
+        if !mode.grows() {
+            return;
+        }
+        for mut len in &mut snake {
+            let Length(l) = *len;
+            *len = Length(l + FOOD_GROWTH);
+        }
+    }
+}
+
+/// Ends the game for a head that has left the arena. Heads that may leave it
+/// have already been wrapped round by [`move_snake`].
+fn wall_collision_check(
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    occupancy: Res<Occupancy>,
+    segments: Query<(Entity, &Segments), With<PlayerControlled>>,
+) {
+    for (snake, segments) in &segments {
+        let head = segments.head_cell();
+        if !occupancy.in_bounds(head) {
+            game_over_writer.write(GameOverEvent {
+                cause: DeathCause::Wall,
+                snake,
+                cell: head,
+            });
+        }
+    }
+}
+
+fn self_collision_check(
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    occupancy: Res<Occupancy>,
+    mode: Res<GameMode>,
+    segments: Query<(Entity, &Segments), (With<PlayerControlled>, Without<Ghost>)>,
+) {
+    if !mode.deadly() {
+        return;
+    }
+    for (snake, segments) in &segments {
+        let head = segments.head_cell();
+        // The head itself accounts for one.
+        if occupancy.segments_at(head) > 1 {
+            game_over_writer.write(GameOverEvent {
+                cause: DeathCause::SelfCollision,
+                snake,
+                cell: head,
+            });
+        }
+    }
+}
+
+fn hazard_collision_check(
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    occupancy: Res<Occupancy>,
+    mode: Res<GameMode>,
+    spikes: Query<&Spike>,
+    segments: Query<(Entity, &Segments), With<PlayerControlled>>,
+) {
+    if !mode.deadly() {
+        return;
+    }
+    for (snake, segments) in &segments {
+        let head = segments.head_cell();
+        let hit = occupancy
+            .hazard_at(head)
+            .filter(|&hazard| !spikes.get(hazard).is_ok_and(|spike| !spike.raised()))
+            .map(|_| head)
+            // Patrols move after the snake, so one can walk into the body.
+            // That also catches a patrol and the head swapping cells, as the
+            // patrol lands where the head just was.
+            .or_else(|| {
+                segments
+                    .cells()
+                    .rev()
+                    .find(|&cell| occupancy.patrols_at(cell) > 0)
+            });
+        if let Some(cell) = hit {
+            game_over_writer.write(GameOverEvent {
+                cause: DeathCause::Hazard,
+                snake,
+                cell,
+            });
+        }
+    }
+}
+
+fn length_check(
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    segments: Query<(Entity, &Segments, &Length), With<PlayerControlled>>,
+) {
+    for (snake, segments, len) in &segments {
+        if len.0 <= 0 {
+            game_over_writer.write(GameOverEvent {
+                cause: DeathCause::Starved,
+                snake,
+                cell: segments.head_cell(),
+            });
+        }
+    }
+}
+
+fn game_over_check(
+    mut commands: Commands,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    crash_sound: Res<CrashSound>,
+    options: Res<GameOptions>,
+    names: Query<&Name>,
+) {
+    // Several checks can fire on the same tick; the first one wins.
+    if let Some(event) = game_over_reader.read().next() {
+        let name = names.get(event.snake).map_or("snake", |n| n.as_str());
+        info!("{name} died: {:?} at {}", event.cause, event.cell);
+        commands.insert_resource(LastDeath(*event));
+        next_state.set(GameState::Dying);
+        commands.spawn(positional_sound_effect(
+            &crash_sound.0,
+            &options,
+            cell_to_world(event.cell),
+            1.,
+        ));
+    }
+}
+
+#[derive(Resource)]
+pub struct EatSound {
+    regular: Handle<AudioSource>,
+    bonus: Handle<AudioSource>,
+}
+
+impl EatSound {
+    fn for_kind(&self, kind: FoodKind) -> &Handle<AudioSource> {
+        match kind {
+            FoodKind::Regular => &self.regular,
+            FoodKind::Bonus => &self.bonus,
+        }
+    }
+}
+#[derive(Resource)]
+pub struct MenuRolloverSound(Handle<AudioSource>);
+#[derive(Resource)]
+pub struct CrashSound(Handle<AudioSource>);
+#[derive(Resource)]
+pub struct StartSound(Handle<AudioSource>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hazard::Patrol;
+    use crate::headless::{asset_counts, headless_game, headless_game_without_food, tick_growing};
+    use crate::powerup::Effect;
+    use bevy::{
+        ecs::system::RunSystemOnce,
+        input::{
+            gamepad::{
+                GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent,
+                RawGamepadButtonChangedEvent, RawGamepadEvent,
+            },
+            InputPlugin,
+        },
+    };
+
+    #[test]
+    fn segments_keep_head_at_back_while_growing_and_shrinking() {
+        let [a, b, c] = [0, 1, 2].map(Entity::from_raw);
+        let mut segments = Segments::new(a, IVec2::ZERO);
+        assert_eq!((segments.head(), segments.tail()), (a, a));
+        assert_eq!(segments.advance(IVec2::Y), (a, IVec2::ZERO));
+        assert_eq!(segments.head_cell(), IVec2::Y);
+
+        segments.grow(b, IVec2::new(0, 2));
+        segments.grow(c, IVec2::new(1, 2));
+        assert_eq!((segments.head(), segments.tail()), (c, a));
+        assert_eq!(segments.iter_body().collect::<Vec<_>>(), [a, b, c]);
+        assert_eq!(
+            segments.cells().collect::<Vec<_>>(),
+            [IVec2::Y, IVec2::new(0, 2), IVec2::new(1, 2)]
+        );
+
+        // The tail moves to the front.
+        assert_eq!(segments.advance(IVec2::new(2, 2)), (a, IVec2::Y));
+        assert_eq!((segments.head(), segments.tail()), (a, b));
+        assert_eq!(segments.head_cell(), IVec2::new(2, 2));
+
+        assert_eq!(segments.shrink(), Some((b, IVec2::new(0, 2))));
+        assert_eq!(segments.shrink(), Some((c, IVec2::new(1, 2))));
+        // The head is never removed.
+        assert_eq!(segments.shrink(), None);
+        assert_eq!((segments.head(), segments.tail()), (a, a));
+    }
+
+    #[test]
+    fn head_leads_as_snake_grows() {
+        // No food, so nothing but the starting length makes it grow.
+        let mut app = headless_game_without_food();
+        for tick in 0..15i32 {
+            app.update();
+            let world = app.world_mut();
+            let segments = world.query::<&Segments>().single(world).unwrap();
+            // The snake starts with one segment and grows to its length of 10.
+            assert_eq!(segments.len(), (tick as usize + 2).min(10));
+            assert_eq!(segments.head_cell(), IVec2::new(0, tick + 1));
+            assert_eq!(
+                world.get::<Transform>(segments.head()).unwrap().translation,
+                cell_to_world(segments.head_cell()).extend(0.)
+            );
+        }
+    }
+
+    #[test]
+    fn body_follows_the_head_through_a_portal() {
+        let mut app = headless_game_without_food();
+        let (entry, exit) = (IVec2::new(0, 3), IVec2::new(5, -5));
+        let mut occupancy = app.world_mut().resource_mut::<Occupancy>();
+        occupancy.set_portal(entry, Some(exit));
+        occupancy.set_portal(exit, Some(entry));
+        for _ in 0..3 {
+            app.update();
+        }
+        let world = app.world_mut();
+        let segments = world.query::<&Segments>().single(world).unwrap();
+        // Straight out of the far portal, gliding from inside it.
+        assert_eq!(segments.head_cell(), IVec2::new(5, -4));
+        assert_eq!(
+            world
+                .get::<PreviousGridPosition>(segments.head())
+                .unwrap()
+                .0,
+            exit
+        );
+
+        for _ in 0..6 {
+            app.update();
+        }
+        let world = app.world_mut();
+        let segments = world.query::<&Segments>().single(world).unwrap();
+        let cells: Vec<IVec2> = segments.cells().collect();
+        assert_eq!(
+            cells,
+            [0, 1, 2]
+                .map(|y| IVec2::new(0, y))
+                .into_iter()
+                .chain((-4..=2).map(|y| IVec2::new(5, y)))
+                .collect::<Vec<_>>()
+        );
+        let occupancy = world.resource::<Occupancy>();
+        assert_eq!(
+            occupancy.segments_at(entry) + occupancy.segments_at(exit),
+            0
+        );
+        for cell in &cells {
+            assert_eq!(occupancy.segments_at(*cell), 1);
+        }
+
+        // Once it stops growing, the segment just past the portal slides out
+        // of it rather than across the arena.
+        app.update();
+        let world = app.world_mut();
+        let segments = world.query::<&Segments>().single(world).unwrap();
+        let (after, cell) = segments.0[2];
+        assert_eq!(cell, IVec2::new(5, -4));
+        assert_eq!(world.get::<PreviousGridPosition>(after).unwrap().0, exit);
+    }
+
+    #[test]
+    fn head_wraps_round_before_it_is_drawn_or_eats() {
+        // The head goes up from the origin a cell a tick, and leaves an
+        // arena three cells high on the third.
+        let wrap = |mode: GameMode, shielded: bool| {
+            let mut app = headless_game_without_food();
+            app.insert_resource(mode);
+            let world = app.world_mut();
+            let food = world.spawn(Food).id();
+            let mut occupancy = Occupancy::new(IVec2::new(-2, -2), IVec2::new(2, 2));
+            occupancy.add_segment(IVec2::ZERO);
+            occupancy.set_food(IVec2::new(0, -2), Some(food));
+            world.insert_resource(occupancy);
+            let snake = world
+                .query::<(Entity, &Segments)>()
+                .single(world)
+                .unwrap()
+                .0;
+            if shielded {
+                world
+                    .entity_mut(snake)
+                    .insert(Shield::new(Timer::from_seconds(1e6, TimerMode::Once)));
+            }
+            for _ in 0..3 {
+                app.update();
+            }
+            let world = app.world_mut();
+            let segments = world.query::<&Segments>().single(world).unwrap();
+            let head = segments.head();
+            assert_eq!(segments.head_cell(), IVec2::new(0, -2));
+            assert_eq!(
+                world.get::<Transform>(head).unwrap().translation,
+                cell_to_world(IVec2::new(0, -2)).extend(0.)
+            );
+            assert!(world.get_entity(food).is_err());
+            world.entity(snake).contains::<Shield>()
+        };
+        assert!(!wrap(GameMode::Zen, false));
+        // A shield is used up by it.
+        assert!(!wrap(GameMode::Classic, true));
+    }
+
+    #[test]
+    fn patrols_cannot_pass_through_the_snake() {
+        let game_with_patrol = |corners: &[IVec2]| {
+            let mut app = headless_game_without_food();
+            app.add_systems(
+                Update,
+                (move_patrols, hazard_collision_check)
+                    .chain()
+                    .after(move_snake),
+            );
+            let patrol = Patrol::new(corners);
+            let at = patrol.cell();
+            let world = app.world_mut();
+            world.spawn((patrol, GridPosition(at), Transform::default()));
+            world.resource_mut::<Occupancy>().add_patrol(at);
+            app
+        };
+        // Where the snake died on each of the first three ticks, if it did.
+        let deaths = |mut app: App| {
+            (0..3)
+                .map(|_| {
+                    app.update();
+                    app.world()
+                        .resource::<Events<GameOverEvent>>()
+                        .iter_current_update_events()
+                        .map(|event| event.cell)
+                        .next()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The head goes up from the origin a cell a tick, and the patrol
+        // comes down to meet it, swapping cells with it on the third tick.
+        let swap = game_with_patrol(&[IVec2::new(0, 5), IVec2::new(0, -5)]);
+        assert_eq!(deaths(swap), [None, None, Some(IVec2::new(0, 2))]);
+        // Crossing the body behind the head is just as deadly.
+        let crossing = game_with_patrol(&[IVec2::new(-3, 1), IVec2::new(3, 1)]);
+        assert_eq!(deaths(crossing), [None, None, Some(IVec2::new(0, 1))]);
+    }
+
+    #[test]
+    fn snake_sheds_a_segment_per_tick_when_length_drops() {
+        let mut app = headless_game_without_food();
+        for _ in 0..12 {
+            app.update();
+        }
+        let world = app.world_mut();
+        world.query::<&mut Length>().single_mut(world).unwrap().0 = 4;
+        for expected in (4..10).rev() {
+            app.update();
+            let world = app.world_mut();
+            let segments = world.query::<&Segments>().single(world).unwrap();
+            assert_eq!(segments.len(), expected);
+            let head = segments.head_cell();
+            let cells: Vec<IVec2> = segments.cells().collect();
+            let spawned = world
+                .query_filtered::<(), With<PreviousGridPosition>>()
+                .iter(world)
+                .count();
+            assert_eq!(spawned, expected);
+            let occupancy = world.resource::<Occupancy>();
+            assert_eq!(occupancy.segments_at(head), 1);
+            for cell in cells {
+                assert_eq!(occupancy.segments_at(cell), 1);
+            }
+        }
+
+        // Starving stops shrinking at the minimum and ends the game.
+        let world = app.world_mut();
+        world.query::<&mut Length>().single_mut(world).unwrap().0 = 0;
+        for _ in 0..5 {
+            app.update();
+        }
+        let world = app.world_mut();
+        let segments = world.query::<&Segments>().single(world).unwrap();
+        assert_eq!(segments.len(), MIN_LENGTH as usize);
+        let events = world.resource::<Events<GameOverEvent>>();
+        let mut cursor = events.get_cursor();
+        assert!(cursor
+            .read(events)
+            .any(|event| event.cause == DeathCause::Starved));
+    }
+
+    /// An app that only steers snakes, fed by gamepads the test plugs in.
+    fn steering_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .init_resource::<input::InputMap>()
+            .add_systems(Update, (assign_gamepads, input_direction).chain());
+        app
+    }
+
+    fn connect_gamepad(app: &mut App) -> Entity {
+        let gamepad = app.world_mut().spawn_empty().id();
+        app.world_mut().send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Test gamepad".into(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        app.update();
+        gamepad
+    }
+
+    fn send_gamepad(app: &mut App, event: RawGamepadEvent) {
+        app.world_mut().send_event(event);
+        app.update();
+    }
+
+    #[test]
+    fn gamepads_steer_their_own_snake() {
+        let mut app = steering_app();
+        let snakes = [(); 2].map(|_| {
+            app.world_mut()
+                .spawn((Direction::North, PlayerControlled))
+                .id()
+        });
+        connect_gamepad(&mut app);
+        connect_gamepad(&mut app);
+        let [a, b] = snakes.map(|snake| app.world().get::<AssignedGamepad>(snake).unwrap().0);
+        assert_ne!(a, b);
+        let heading = |app: &App, i: usize, dir: Direction| {
+            app.world().get::<Direction>(snakes[i]) == Some(&dir)
+        };
+
+        // Past the dead zone the stick snaps to its strongest axis.
+        send_gamepad(
+            &mut app,
+            RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                a,
+                GamepadAxis::LeftStickX,
+                -0.9,
+            )),
+        );
+        assert!(heading(&app, 0, Direction::West));
+        assert!(heading(&app, 1, Direction::North));
+
+        // Reversing is ignored, as with the keyboard.
+        send_gamepad(
+            &mut app,
+            RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                a,
+                GamepadAxis::LeftStickX,
+                0.9,
+            )),
+        );
+        assert!(heading(&app, 0, Direction::West));
+
+        send_gamepad(
+            &mut app,
+            RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                b,
+                GamepadAxis::LeftStickX,
+                0.3,
+            )),
+        );
+        assert!(heading(&app, 1, Direction::North));
+
+        send_gamepad(
+            &mut app,
+            RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                b,
+                GamepadButton::DPadRight,
+                1.0,
+            )),
+        );
+        assert!(heading(&app, 1, Direction::East));
+        assert!(heading(&app, 0, Direction::West));
+
+        // An unplugged gamepad frees its snake.
+        app.world_mut().send_event(GamepadConnectionEvent::new(
+            a,
+            GamepadConnection::Disconnected,
+        ));
+        app.update();
+        app.update();
+        assert!(app.world().get::<AssignedGamepad>(snakes[0]).is_none());
+        assert!(app.world().get::<AssignedGamepad>(snakes[1]).is_some());
+    }
+
+    /// Where food has landed after twenty ticks, in order. The snake takes
+    /// the same path every time, so only the seed decides where.
+    fn food_cells(mut app: App) -> Vec<(i32, i32)> {
+        for _ in 0..20 {
+            app.update();
+        }
+        let world = app.world_mut();
+        let mut cells: Vec<(i32, i32)> = world
+            .query_filtered::<&GridPosition, With<Food>>()
+            .iter(world)
+            .map(|position| (position.0.x, position.0.y))
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn seeded_food_comes_in_the_same_sequence() {
+        let food_cells = |seed| {
+            let mut app = headless_game();
+            app.insert_resource(GameRng::new(Some(seed)));
+            food_cells(app)
+        };
+        let cells = food_cells(20_744);
+        assert!(cells.len() > 2);
+        assert_eq!(cells, food_cells(20_744));
+        assert_ne!(cells, food_cells(20_745));
+    }
+
+    #[test]
+    fn daily_food_is_the_same_whatever_the_window_size() {
+        let food_cells = |width: f32, height: f32| {
+            let mut app = headless_game();
+            app.insert_resource(DailyAttempt {
+                day: 20_744,
+                seed: 20_744,
+                official: false,
+            });
+            let world = app.world_mut();
+            world
+                .query::<&mut Window>()
+                .single_mut(world)
+                .unwrap()
+                .resolution
+                .set(width, height);
+            world.run_system_once(reset_occupancy).unwrap();
+            world.run_system_once(reset_spawns).unwrap();
+            world.resource_mut::<Occupancy>().add_segment(IVec2::ZERO);
+            food_cells(app)
+        };
+        let cells = food_cells(800., 600.);
+        assert!(cells.len() > 2);
+        assert_eq!(cells, food_cells(1920., 1080.));
+    }
+
+    #[test]
+    fn asset_count_stays_flat_over_long_game() {
+        const TICKS: usize = 2000;
+        let mut app = headless_game();
+        app.update();
+        let before = asset_counts(&app);
+
+        for _ in 0..TICKS {
+            tick_growing(&mut app);
+        }
+
+        let world = app.world_mut();
+        let segments: usize = world
+            .query::<&Segments>()
+            .iter(world)
+            .map(|s| s.len())
+            .sum();
+        let food = world.query::<&Food>().iter(world).count();
+        assert!(segments > TICKS / 2);
+        assert!(food > 0);
+        assert_eq!(before, asset_counts(&app));
+    }
+}
@@ -0,0 +1,197 @@
+//! Data-driven menu screens. A [`MenuSpec`] lists a title and buttons, and
+//! pressing a button sends its [`MenuAction`].
+use bevy::prelude::*;
+
+use crate::input::Action;
+use crate::options::{sound_effect, GameOptions, Setting};
+use crate::theme::CurrentTheme;
+use crate::MenuRolloverSound;
+
+/// What a menu button does. Each screen handles the ones it shows.
+#[derive(Event, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MenuAction {
+    Start,
+    Resume,
+    Quit,
+    MainMenu,
+    Controls,
+    Options,
+    /// Leaves a screen for the one it was opened from.
+    Back,
+    CycleTheme,
+    CycleLevel,
+    CycleMode,
+    DailyChallenge,
+    Rebind(Action),
+    TogglePointerSteering,
+    Adjust(Setting),
+    /// Steps a setting the other way.
+    AdjustDown(Setting),
+}
+
+pub struct MenuSpec {
+    pub title: Option<String>,
+    pub items: Vec<(String, MenuAction)>,
+}
+
+/// Root of a spawned menu.
+#[derive(Component)]
+pub struct MenuScreen;
+
+/// A button and the action it sends.
+#[derive(Component)]
+pub struct MenuItem(pub MenuAction);
+
+/// The text of a button, found by its action to relabel it.
+#[derive(Component)]
+pub struct MenuItemLabel(pub MenuAction);
+
+/// Text in a menu, restyled when the theme changes.
+#[derive(Component)]
+pub struct MenuText;
+
+impl MenuSpec {
+    pub fn new(title: Option<&str>) -> Self {
+        MenuSpec {
+            title: title.map(str::to_string),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn item(mut self, label: impl Into<String>, action: MenuAction) -> Self {
+        self.items.push((label.into(), action));
+        self
+    }
+
+    /// Spawns the screen, centred in a column, and returns its root.
+    pub fn spawn(self, commands: &mut Commands, theme: &CurrentTheme) -> Entity {
+        commands
+            .spawn((
+                MenuScreen,
+                Node {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                if let Some(title) = self.title {
+                    parent.spawn((
+                        MenuText,
+                        Text::new(title),
+                        theme.font.clone(),
+                        TextColor(theme.text),
+                        Node {
+                            margin: UiRect::bottom(Val::Px(20.)),
+                            ..default()
+                        },
+                    ));
+                }
+                for (label, action) in self.items {
+                    parent
+                        .spawn((
+                            MenuItem(action),
+                            Button,
+                            Node {
+                                width: Val::Px(400.),
+                                height: Val::Px(55.),
+                                margin: UiRect::all(Val::Px(4.)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(theme.normal_button),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                MenuItemLabel(action),
+                                MenuText,
+                                Text::new(label),
+                                theme.font.clone(),
+                                TextColor(theme.text),
+                            ));
+                        });
+                }
+            })
+            .id()
+    }
+}
+
+/// Sends the action of any pressed button, and plays the rollover sound when
+/// one is hovered. Button colors are left to [`crate::focus::highlight_focus`].
+pub fn menu_interaction(
+    mut commands: Commands,
+    mut actions: EventWriter<MenuAction>,
+    items: Query<(&Interaction, &MenuItem), Changed<Interaction>>,
+    menu_sound: Res<MenuRolloverSound>,
+    options: Res<GameOptions>,
+) {
+    for (interaction, item) in &items {
+        match *interaction {
+            Interaction::Pressed => {
+                actions.write(item.0);
+            }
+            Interaction::Hovered => {
+                commands.spawn(sound_effect(&menu_sound.0, &options));
+            }
+            Interaction::None => {}
+        }
+    }
+}
+
+/// Changes the label of every button that sends `action`.
+pub fn relabel(labels: &mut Query<(&mut Text, &MenuItemLabel)>, action: MenuAction, label: &str) {
+    for (mut text, item) in labels.iter_mut() {
+        if item.0 == action && text.0 != label {
+            text.0 = label.to_string();
+        }
+    }
+}
+
+pub fn restyle_menus(
+    theme: Res<CurrentTheme>,
+    mut texts: Query<(&mut TextFont, &mut TextColor), With<MenuText>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for (mut font, mut color) in &mut texts {
+        *font = theme.font.clone();
+        *color = TextColor(theme.text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressing_an_item_sends_its_action() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<MenuAction>()
+            .insert_resource(MenuRolloverSound(Handle::default()))
+            .init_resource::<GameOptions>()
+            .add_systems(Update, menu_interaction);
+        app.world_mut()
+            .spawn((MenuItem(MenuAction::Start), Interaction::None));
+        let quit = app
+            .world_mut()
+            .spawn((MenuItem(MenuAction::Quit), Interaction::None))
+            .id();
+        app.update();
+        app.world_mut().resource_mut::<Events<MenuAction>>().clear();
+
+        *app.world_mut().get_mut::<Interaction>(quit).unwrap() = Interaction::Pressed;
+        app.update();
+        let sent: Vec<MenuAction> = app
+            .world_mut()
+            .resource_mut::<Events<MenuAction>>()
+            .drain()
+            .collect();
+        assert_eq!(sent, [MenuAction::Quit]);
+    }
+}
@@ -0,0 +1,148 @@
+//! Game modes, picked on the main menu: the classic endless game, a race for
+//! points against the clock, surviving an arena that fills up with
+//! obstacles, and a relaxed game with no way to die.
+use bevy::prelude::*;
+
+use crate::menu::MenuText;
+use crate::theme::CurrentTheme;
+use crate::{CleanupOnRestart, DeathCause, GameOverEvent, PlayerControlled, Segments};
+
+/// How long a game of Time Attack lasts.
+const TIME_ATTACK_SECS: f32 = 120.;
+
+#[derive(Resource, Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum GameMode {
+    /// Play until the snake dies.
+    #[default]
+    Classic,
+    /// Score as much as possible before time runs out.
+    TimeAttack,
+    /// Food no longer makes the snake longer, and obstacles keep appearing.
+    Survival,
+    /// Walls wrap around and the snake can cross itself.
+    Zen,
+}
+
+impl GameMode {
+    const ALL: [GameMode; 4] = [
+        GameMode::Classic,
+        GameMode::TimeAttack,
+        GameMode::Survival,
+        GameMode::Zen,
+    ];
+
+    pub fn cycle(&mut self) {
+        let i = GameMode::ALL.iter().position(|mode| mode == self).unwrap();
+        *self = GameMode::ALL[(i + 1) % GameMode::ALL.len()];
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Survival => "Survival",
+            GameMode::Zen => "Zen",
+        }
+    }
+
+    /// Whether eating makes the snake longer.
+    pub fn grows(self) -> bool {
+        self != GameMode::Survival
+    }
+
+    /// Whether running into things ends the game.
+    pub fn deadly(self) -> bool {
+        self != GameMode::Zen
+    }
+}
+
+/// What is left of a Time Attack game.
+#[derive(Resource)]
+pub struct TimeLimit(Timer);
+
+#[derive(Component)]
+pub struct CountdownText;
+
+fn countdown_label(limit: &TimeLimit) -> String {
+    let secs = limit.0.remaining_secs().ceil() as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Starts the clock, and shows it at the top of the screen in Time Attack.
+pub fn setup_countdown(mut commands: Commands, mode: Res<GameMode>, theme: Res<CurrentTheme>) {
+    let limit = TimeLimit(Timer::from_seconds(TIME_ATTACK_SECS, TimerMode::Once));
+    if *mode == GameMode::TimeAttack {
+        commands
+            .spawn((
+                Name::new("countdown"),
+                CleanupOnRestart,
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+            ))
+            .with_child((
+                CountdownText,
+                MenuText,
+                Text::new(countdown_label(&limit)),
+                theme.font.clone(),
+                TextColor(theme.text),
+            ));
+    }
+    commands.insert_resource(limit);
+}
+
+/// Counts down the Time Attack clock and ends the game when it runs out.
+pub fn run_clock(
+    time: Res<Time>,
+    mut limit: ResMut<TimeLimit>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    snakes: Query<(Entity, &Segments), With<PlayerControlled>>,
+    mut texts: Query<&mut Text, With<CountdownText>>,
+) {
+    limit.0.tick(time.delta());
+    let label = countdown_label(&limit);
+    for mut text in &mut texts {
+        if text.0 != label {
+            text.0 = label.clone();
+        }
+    }
+    if !limit.0.just_finished() {
+        return;
+    }
+    for (snake, segments) in &snakes {
+        game_over_writer.write(GameOverEvent {
+            cause: DeathCause::TimeUp,
+            snake,
+            cell: segments.head_cell(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_cycle_and_set_the_rules() {
+        let mut mode = GameMode::default();
+        let mut seen = vec![mode];
+        for _ in 0..4 {
+            mode.cycle();
+            seen.push(mode);
+        }
+        assert_eq!(seen.first(), seen.last());
+        assert_eq!(&seen[..4], GameMode::ALL);
+        assert!(!GameMode::Survival.grows());
+        assert!(!GameMode::Zen.deadly());
+        assert!(GameMode::TimeAttack.grows() && GameMode::TimeAttack.deadly());
+
+        let mut limit = TimeLimit(Timer::from_seconds(TIME_ATTACK_SECS, TimerMode::Once));
+        assert_eq!(countdown_label(&limit), "2:00");
+        limit.0.tick(std::time::Duration::from_secs_f32(110.5));
+        assert_eq!(countdown_label(&limit), "0:10");
+    }
+}
@@ -0,0 +1,390 @@
+//! Looping background music for each screen, crossfaded between screens,
+//! ducked while paused and built up in layers as the snake grows. The tracks
+//! are played on the synthesizer at startup.
+use bevy::{audio::Volume, prelude::*};
+
+use crate::options::{GameOptions, OptionsReturn};
+use crate::synth::{render_loop, wav, Envelope, Tone, Waveform};
+use crate::{GameState, PlayerControlled, Segments};
+
+const CROSSFADE_SECS: f32 = 1.5;
+/// How loud the game music stays while paused.
+const PAUSE_DUCK: f32 = 0.3;
+/// How long the snake has to be for each layer of the game music to play.
+const LAYER_LENGTHS: [usize; 3] = [0, 20, 40];
+/// How much faster the game music plays once the snake is
+/// `FULL_TEMPO_LENGTH` long.
+const MAX_SPEEDUP: f32 = 0.1;
+const FULL_TEMPO_LENGTH: usize = 100;
+
+const MENU_BPM: f32 = 96.;
+const GAME_BPM: f32 = 128.;
+const GAME_OVER_BPM: f32 = 70.;
+/// How loud the loudest moment of every track is.
+const MUSIC_PEAK: f32 = 0.6;
+
+/// Dies away over a note, for a soft piano-like arpeggio.
+const FADING: Envelope = Envelope {
+    attack: 0.01,
+    decay: 0.6,
+    sustain: 0.,
+    release: 0.2,
+};
+/// Like [`FADING`], slow enough to carry a bass note through a bar.
+const DRONE: Envelope = Envelope {
+    attack: 0.01,
+    decay: 2.5,
+    sustain: 0.,
+    release: 0.2,
+};
+/// Holds steady until a quick release at the end of the note.
+const HELD: Envelope = Envelope {
+    attack: 0.01,
+    decay: 0.,
+    sustain: 1.,
+    release: 0.06,
+};
+const KICK: Envelope = Envelope {
+    attack: 0.002,
+    decay: 0.13,
+    sustain: 0.,
+    release: 0.02,
+};
+const HAT: Envelope = Envelope {
+    attack: 0.001,
+    decay: 0.04,
+    sustain: 0.,
+    release: 0.01,
+};
+
+/// The frequency of a MIDI note, where 69 is the A above middle C.
+fn note_hz(note: i32) -> f32 {
+    440. * 2f32.powf((note - 69) as f32 / 12.)
+}
+
+/// A gentle arpeggio over C, Am, F and G, 16 beats long.
+fn menu_tones() -> Vec<Tone> {
+    let beat = 60. / MENU_BPM;
+    let chords = [[60, 64, 67], [57, 60, 64], [53, 57, 60], [55, 59, 62]];
+    let mut tones = Vec::new();
+    for (bar, chord) in chords.iter().enumerate() {
+        let start = bar as f32 * 4. * beat;
+        for step in 0..8 {
+            let note = chord[[0, 1, 2, 1][step % 4]] + 12;
+            tones.push(
+                Tone::new(
+                    Waveform::Sine,
+                    note_hz(note),
+                    start + step as f32 * 0.5 * beat,
+                    0.5 * beat,
+                )
+                .envelope(FADING)
+                .volume(0.4),
+            );
+        }
+        tones.push(
+            Tone::new(Waveform::Sine, note_hz(chord[0] - 12), start, 4. * beat)
+                .envelope(DRONE)
+                .volume(0.3),
+        );
+    }
+    tones
+}
+
+/// The three layers of the game music: a bass line over A, A, F and G, then
+/// drums, then a lead. Each is 16 beats long so they stay together.
+fn game_layer_tones() -> [Vec<Tone>; 3] {
+    let beat = 60. / GAME_BPM;
+    let mut bass = Vec::new();
+    for (bar, root) in [45, 45, 41, 43].into_iter().enumerate() {
+        for step in 0..8 {
+            let note = root + if step % 2 == 1 { 12 } else { 0 };
+            let start = (bar as f32 * 4. + step as f32 * 0.5) * beat;
+            bass.push(
+                Tone::new(Waveform::Triangle, note_hz(note), start, 0.45 * beat)
+                    .envelope(HELD)
+                    .volume(0.5),
+            );
+        }
+    }
+    let mut drums = Vec::new();
+    for step in 0..16 {
+        let start = step as f32 * beat;
+        drums.push(
+            Tone::new(Waveform::Sine, 150., start, 0.15)
+                .slide_to(50.)
+                .envelope(KICK)
+                .volume(0.8),
+        );
+        drums.push(
+            Tone::new(Waveform::Noise, 0., start + 0.5 * beat, 0.05)
+                .envelope(HAT)
+                .volume(0.25),
+        );
+    }
+    let line = [
+        69, 72, 76, 74, 72, 69, 67, 69, 65, 69, 72, 69, 67, 71, 74, 71,
+    ];
+    let lead = line
+        .into_iter()
+        .enumerate()
+        .map(|(step, note)| {
+            Tone::new(
+                Waveform::Square,
+                note_hz(note + 12),
+                step as f32 * beat,
+                0.9 * beat,
+            )
+            .envelope(HELD)
+            .volume(0.2)
+        })
+        .collect();
+    [bass, drums, lead]
+}
+
+/// A slow fall through A minor over a held chord, 8 beats long.
+fn game_over_tones() -> Vec<Tone> {
+    let beat = 60. / GAME_OVER_BPM;
+    let slow = Envelope {
+        attack: 0.05,
+        release: 0.5,
+        ..HELD
+    };
+    let fall = [69, 67, 64, 60].into_iter().enumerate().map(|(i, note)| {
+        Tone::new(
+            Waveform::Sine,
+            note_hz(note),
+            i as f32 * 2. * beat,
+            2. * beat,
+        )
+        .volume(0.4)
+    });
+    let chord = [(45, 0.3), (52, 0.2)].into_iter().map(|(note, volume)| {
+        Tone::new(Waveform::Sine, note_hz(note), 0., 8. * beat).volume(volume)
+    });
+    fall.chain(chord).map(|tone| tone.envelope(slow)).collect()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Track {
+    Menu,
+    Game,
+    GameOver,
+}
+
+#[derive(Resource)]
+pub struct MusicTracks {
+    menu: Handle<AudioSource>,
+    /// Played together, each one fading in at its `LAYER_LENGTHS`.
+    game_layers: [Handle<AudioSource>; 3],
+    game_over: Handle<AudioSource>,
+}
+
+impl MusicTracks {
+    fn layers(&self, track: Track) -> &[Handle<AudioSource>] {
+        match track {
+            Track::Menu => std::slice::from_ref(&self.menu),
+            Track::Game => &self.game_layers,
+            Track::GameOver => std::slice::from_ref(&self.game_over),
+        }
+    }
+}
+
+/// A playing layer of a track and how loud it is, before the master volume.
+#[derive(Component)]
+pub struct Music {
+    track: Track,
+    layer: usize,
+    volume: f32,
+}
+
+pub fn synthesize_music(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let mut add = |tones: Vec<Tone>, beats: f32, bpm: f32| {
+        let samples = render_loop(&tones, beats * 60. / bpm, MUSIC_PEAK);
+        sources.add(AudioSource {
+            bytes: wav(&samples).into(),
+        })
+    };
+    let [base, drums, lead] = game_layer_tones();
+    commands.insert_resource(MusicTracks {
+        menu: add(menu_tones(), 16., MENU_BPM),
+        game_layers: [
+            add(base, 16., GAME_BPM),
+            add(drums, 16., GAME_BPM),
+            add(lead, 16., GAME_BPM),
+        ],
+        game_over: add(game_over_tones(), 8., GAME_OVER_BPM),
+    });
+}
+
+/// The track a state plays, and whether it is ducked.
+fn wanted_track(state: GameState, options_return: Option<&OptionsReturn>) -> (Track, bool) {
+    match state {
+        GameState::Menu | GameState::Controls => (Track::Menu, false),
+        // The options opened from the pause menu keep the paused game playing.
+        GameState::Options => match options_return {
+            Some(OptionsReturn(GameState::Pause)) => (Track::Game, true),
+            _ => (Track::Menu, false),
+        },
+        GameState::StartGame | GameState::InGame | GameState::Dying => (Track::Game, false),
+        GameState::Pause => (Track::Game, true),
+        GameState::GameOver => (Track::GameOver, false),
+    }
+}
+
+/// Starts the track for the current state, silent so it can fade in, unless
+/// it is already playing or still fading out.
+pub fn switch_music(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    options_return: Option<Res<OptionsReturn>>,
+    tracks: Res<MusicTracks>,
+    music: Query<&Music>,
+) {
+    let (track, _) = wanted_track(*state.get(), options_return.as_deref());
+    if music.iter().any(|music| music.track == track) {
+        return;
+    }
+    for (layer, handle) in tracks.layers(track).iter().enumerate() {
+        commands.spawn((
+            Name::new("music"),
+            Music {
+                track,
+                layer,
+                volume: 0.,
+            },
+            AudioPlayer(handle.clone()),
+            PlaybackSettings::LOOP.with_volume(Volume::SILENT),
+        ));
+    }
+}
+
+/// Fades each layer towards how loud it should be, despawning the layers of
+/// tracks that have faded out.
+pub fn fade_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    options_return: Option<Res<OptionsReturn>>,
+    options: Res<GameOptions>,
+    snakes: Query<&Segments, With<PlayerControlled>>,
+    mut music: Query<(Entity, &mut Music, Option<&mut AudioSink>)>,
+) {
+    let (track, ducked) = wanted_track(*state.get(), options_return.as_deref());
+    let length = snakes.iter().map(Segments::len).max().unwrap_or(0);
+    let step = time.delta_secs() / CROSSFADE_SECS;
+    for (entity, mut music, sink) in &mut music {
+        let target = if music.track != track || length < LAYER_LENGTHS[music.layer] {
+            0.
+        } else if ducked {
+            PAUSE_DUCK
+        } else {
+            1.
+        };
+        music.volume = if music.volume < target {
+            (music.volume + step).min(target)
+        } else {
+            (music.volume - step).max(target)
+        };
+        if music.track != track && music.volume == 0. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let Some(mut sink) = sink else {
+            continue;
+        };
+        sink.set_volume(Volume::Linear(
+            music.volume * f32::from(options.master_volume) / 100.,
+        ));
+        if music.track == Track::Game {
+            let intensity = length.min(FULL_TEMPO_LENGTH) as f32 / FULL_TEMPO_LENGTH as f32;
+            sink.set_speed(1. + MAX_SPEEDUP * intensity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    fn volumes(app: &mut App) -> Vec<(Track, usize, f32)> {
+        let world = app.world_mut();
+        let mut volumes: Vec<_> = world
+            .query::<&Music>()
+            .iter(world)
+            .map(|music| (music.track, music.layer, music.volume))
+            .collect();
+        volumes.sort_by_key(|&(track, layer, _)| (track as u8, layer));
+        volumes
+    }
+
+    #[test]
+    fn game_layers_loop_together() {
+        let lengths: Vec<usize> = game_layer_tones()
+            .iter()
+            .map(|tones| {
+                let samples = render_loop(tones, 16. * 60. / GAME_BPM, MUSIC_PEAK);
+                let loudest = samples
+                    .iter()
+                    .fold(0., |loudest: f32, s| loudest.max(s.abs()));
+                assert!((loudest - MUSIC_PEAK).abs() < 1e-4);
+                samples.len()
+            })
+            .collect();
+        assert!(lengths.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn tracks_crossfade_between_states() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                CROSSFADE_SECS / 2.,
+            )))
+            .insert_resource(State::new(GameState::Menu))
+            .insert_resource(MusicTracks {
+                menu: Handle::default(),
+                game_layers: default(),
+                game_over: Handle::default(),
+            })
+            .init_resource::<GameOptions>()
+            .add_systems(Update, (switch_music, fade_music).chain());
+        app.world_mut()
+            .resource_mut::<Time<Virtual>>()
+            .set_max_delta(Duration::from_secs(10));
+
+        // The first update has no time passing.
+        app.update();
+        app.update();
+        assert_eq!(volumes(&mut app), [(Track::Menu, 0, 0.5)]);
+        app.update();
+        app.update();
+        assert_eq!(volumes(&mut app), [(Track::Menu, 0, 1.)]);
+
+        // Without a snake only the first layer of the game music plays.
+        app.insert_resource(State::new(GameState::InGame));
+        app.update();
+        assert_eq!(
+            volumes(&mut app),
+            [
+                (Track::Menu, 0, 0.5),
+                (Track::Game, 0, 0.5),
+                (Track::Game, 1, 0.),
+                (Track::Game, 2, 0.),
+            ]
+        );
+        app.insert_resource(State::new(GameState::Pause));
+        app.update();
+        app.update();
+        assert_eq!(
+            volumes(&mut app),
+            [
+                (Track::Game, 0, PAUSE_DUCK),
+                (Track::Game, 1, 0.),
+                (Track::Game, 2, 0.),
+            ]
+        );
+    }
+}
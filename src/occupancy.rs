@@ -0,0 +1,249 @@
+//! A dense grid of what is in each cell of the arena, kept up to date as
+//! things move so lookups never have to scan every segment.
+use bevy::prelude::*;
+
+#[derive(Resource, Default)]
+pub struct Occupancy {
+    min: IVec2,
+    size: UVec2,
+    /// Number of snake segments in each cell. More than one means a collision.
+    segments: Vec<u32>,
+    food: Vec<Option<Entity>>,
+    power_ups: Vec<Option<Entity>>,
+    /// Spikes and obstacles, which may or may not be deadly right now.
+    hazards: Vec<Option<Entity>>,
+    /// Number of patrols in each cell. They walk over whatever else is
+    /// there, so are kept apart from the hazards that stay put.
+    patrols: Vec<u32>,
+    /// Where each portal leads.
+    portals: Vec<Option<IVec2>>,
+}
+
+impl Occupancy {
+    /// An empty grid covering `min..=max`. Cells outside it are never
+    /// occupied and writes to them are ignored.
+    pub fn new(min: IVec2, max: IVec2) -> Self {
+        let size = (max - min + IVec2::ONE).max(IVec2::ZERO).as_uvec2();
+        let cells = size.x as usize * size.y as usize;
+        Occupancy {
+            min,
+            size,
+            segments: vec![0; cells],
+            food: vec![None; cells],
+            power_ups: vec![None; cells],
+            hazards: vec![None; cells],
+            patrols: vec![0; cells],
+            portals: vec![None; cells],
+        }
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let offset = cell - self.min;
+        if offset.x < 0
+            || offset.y < 0
+            || offset.x as u32 >= self.size.x
+            || offset.y as u32 >= self.size.y
+        {
+            return None;
+        }
+        Some(offset.y as usize * self.size.x as usize + offset.x as usize)
+    }
+
+    pub fn in_bounds(&self, cell: IVec2) -> bool {
+        self.index(cell).is_some()
+    }
+
+    /// The lowest and highest cells inside the grid.
+    pub fn bounds(&self) -> (IVec2, IVec2) {
+        (self.min, self.min + self.size.as_ivec2() - IVec2::ONE)
+    }
+
+    /// The cell inside the grid that `cell` lands on when leaving one edge
+    /// comes back in at the opposite one.
+    pub fn wrap(&self, cell: IVec2) -> IVec2 {
+        self.min + (cell - self.min).rem_euclid(self.size.as_ivec2().max(IVec2::ONE))
+    }
+
+    pub fn add_segment(&mut self, cell: IVec2) {
+        if let Some(i) = self.index(cell) {
+            self.segments[i] += 1;
+        }
+    }
+
+    pub fn remove_segment(&mut self, cell: IVec2) {
+        if let Some(i) = self.index(cell) {
+            self.segments[i] = self.segments[i].saturating_sub(1);
+        }
+    }
+
+    pub fn segments_at(&self, cell: IVec2) -> u32 {
+        self.index(cell).map_or(0, |i| self.segments[i])
+    }
+
+    pub fn set_food(&mut self, cell: IVec2, food: Option<Entity>) {
+        if let Some(i) = self.index(cell) {
+            self.food[i] = food;
+        }
+    }
+
+    pub fn food_at(&self, cell: IVec2) -> Option<Entity> {
+        self.index(cell).and_then(|i| self.food[i])
+    }
+
+    pub fn set_power_up(&mut self, cell: IVec2, power_up: Option<Entity>) {
+        if let Some(i) = self.index(cell) {
+            self.power_ups[i] = power_up;
+        }
+    }
+
+    pub fn power_up_at(&self, cell: IVec2) -> Option<Entity> {
+        self.index(cell).and_then(|i| self.power_ups[i])
+    }
+
+    pub fn set_hazard(&mut self, cell: IVec2, hazard: Option<Entity>) {
+        if let Some(i) = self.index(cell) {
+            self.hazards[i] = hazard;
+        }
+    }
+
+    pub fn hazard_at(&self, cell: IVec2) -> Option<Entity> {
+        self.index(cell).and_then(|i| self.hazards[i])
+    }
+
+    pub fn add_patrol(&mut self, cell: IVec2) {
+        if let Some(i) = self.index(cell) {
+            self.patrols[i] += 1;
+        }
+    }
+
+    pub fn remove_patrol(&mut self, cell: IVec2) {
+        if let Some(i) = self.index(cell) {
+            self.patrols[i] = self.patrols[i].saturating_sub(1);
+        }
+    }
+
+    pub fn patrols_at(&self, cell: IVec2) -> u32 {
+        self.index(cell).map_or(0, |i| self.patrols[i])
+    }
+
+    pub fn set_portal(&mut self, cell: IVec2, exit: Option<IVec2>) {
+        if let Some(i) = self.index(cell) {
+            self.portals[i] = exit;
+        }
+    }
+
+    /// The cell the portal in `cell` leads to, if there is one.
+    pub fn portal_at(&self, cell: IVec2) -> Option<IVec2> {
+        self.index(cell).and_then(|i| self.portals[i])
+    }
+
+    /// The cell a snake in `cell` moves into when heading along `step`, coming
+    /// out the far side of a portal if it runs into one.
+    pub fn step(&self, cell: IVec2, step: IVec2) -> IVec2 {
+        let next = cell + step;
+        self.portal_at(next).map_or(next, |exit| exit + step)
+    }
+
+    /// The step a snake took to get from `from` to `to`. They are next to each
+    /// other unless it went through a portal or round the edge of the arena,
+    /// and zero if neither explains the gap.
+    pub fn step_between(&self, from: IVec2, to: IVec2) -> IVec2 {
+        let offset = to - from;
+        if offset.abs().element_sum() == 1 {
+            return offset;
+        }
+        [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .into_iter()
+            .find(|&step| self.step(from, step) == to || self.wrap(from + step) == to)
+            .unwrap_or(IVec2::ZERO)
+    }
+
+    /// Whether something new can be placed in `cell`.
+    pub fn is_free(&self, cell: IVec2) -> bool {
+        self.index(cell).is_some_and(|i| {
+            self.segments[i] == 0
+                && self.food[i].is_none()
+                && self.power_ups[i].is_none()
+                && self.hazards[i].is_none()
+                && self.patrols[i] == 0
+                && self.portals[i].is_none()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_segments_and_food() {
+        let mut occupancy = Occupancy::new(IVec2::new(-2, -2), IVec2::new(2, 2));
+        let cell = IVec2::new(1, -2);
+        assert!(occupancy.is_free(cell));
+        occupancy.add_segment(cell);
+        occupancy.add_segment(cell);
+        assert_eq!(occupancy.segments_at(cell), 2);
+        occupancy.remove_segment(cell);
+        assert_eq!(occupancy.segments_at(cell), 1);
+        occupancy.remove_segment(cell);
+        assert!(occupancy.is_free(cell));
+
+        let food = Entity::from_raw(7);
+        occupancy.set_food(cell, Some(food));
+        assert_eq!(occupancy.food_at(cell), Some(food));
+        assert!(!occupancy.is_free(cell));
+
+        // A patrol passing over a spike leaves it where it was.
+        let spike = Entity::from_raw(8);
+        let cell = IVec2::ZERO;
+        occupancy.set_hazard(cell, Some(spike));
+        occupancy.add_patrol(cell);
+        occupancy.remove_patrol(cell);
+        assert_eq!(occupancy.patrols_at(cell), 0);
+        assert_eq!(occupancy.hazard_at(cell), Some(spike));
+    }
+
+    #[test]
+    fn ignores_cells_out_of_bounds() {
+        let mut occupancy = Occupancy::new(IVec2::new(-2, -2), IVec2::new(2, 2));
+        let outside = IVec2::new(3, 0);
+        occupancy.add_segment(outside);
+        assert_eq!(occupancy.segments_at(outside), 0);
+        assert!(!occupancy.in_bounds(outside));
+        assert!(!occupancy.is_free(outside));
+    }
+
+    #[test]
+    fn wraps_to_the_opposite_edge() {
+        let occupancy = Occupancy::new(IVec2::new(-2, -1), IVec2::new(2, 1));
+        assert_eq!(occupancy.bounds(), (IVec2::new(-2, -1), IVec2::new(2, 1)));
+        assert_eq!(occupancy.wrap(IVec2::new(3, 0)), IVec2::new(-2, 0));
+        assert_eq!(occupancy.wrap(IVec2::new(0, -2)), IVec2::new(0, 1));
+        assert_eq!(occupancy.wrap(IVec2::new(1, 1)), IVec2::new(1, 1));
+        assert_eq!(
+            occupancy.step_between(IVec2::new(2, 0), IVec2::new(-2, 0)),
+            IVec2::X
+        );
+    }
+
+    #[test]
+    fn steps_through_portals() {
+        let mut occupancy = Occupancy::new(IVec2::new(-4, -4), IVec2::new(4, 4));
+        let (a, b) = (IVec2::new(1, 0), IVec2::new(-2, 3));
+        occupancy.set_portal(a, Some(b));
+        occupancy.set_portal(b, Some(a));
+        assert!(!occupancy.is_free(a));
+        // Into one and straight out of the other, still heading the same way.
+        assert_eq!(occupancy.step(IVec2::ZERO, IVec2::X), IVec2::new(-1, 3));
+        assert_eq!(
+            occupancy.step(IVec2::new(-2, 2), IVec2::Y),
+            IVec2::new(1, 1)
+        );
+        assert_eq!(occupancy.step(IVec2::ZERO, IVec2::Y), IVec2::Y);
+        assert_eq!(
+            occupancy.step_between(IVec2::ZERO, IVec2::new(-1, 3)),
+            IVec2::X
+        );
+        assert_eq!(occupancy.step_between(IVec2::ZERO, IVec2::ONE), IVec2::ZERO);
+    }
+}
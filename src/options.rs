@@ -0,0 +1,346 @@
+//! The options screen: volume, window and graphics settings.
+use bevy::{
+    audio::Volume,
+    core_pipeline::bloom::Bloom,
+    prelude::*,
+    window::{MonitorSelection, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Minimap;
+use crate::input::{Action, Actions};
+use crate::menu::{relabel, MenuAction, MenuItem, MenuItemLabel, MenuSpec};
+use crate::theme::CurrentTheme;
+use crate::GameState;
+
+/// Window sizes the resolution button steps through.
+const RESOLUTIONS: [UVec2; 4] = [
+    UVec2::new(960, 540),
+    UVec2::new(1280, 720),
+    UVec2::new(1600, 900),
+    UVec2::new(1920, 1080),
+];
+
+/// World units per unit of distance for positional sound, so that sounds at
+/// the edge of the arena come mostly from one side.
+pub const AUDIO_SCALE: f32 = 1. / 100.;
+/// Distance between the listener's ears, in scaled units.
+pub const EAR_GAP: f32 = 4.;
+
+/// How much each step turns a volume up or down, in percent.
+const VOLUME_STEP: u8 = 10;
+
+/// A setting with a button on the options screen.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Setting {
+    MasterVolume,
+    SfxVolume,
+    Fullscreen,
+    Resolution,
+    Bloom,
+    SpatialAudio,
+    SmoothMovement,
+    SegmentStyle,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 8] = [
+        Setting::MasterVolume,
+        Setting::SfxVolume,
+        Setting::SpatialAudio,
+        Setting::Fullscreen,
+        Setting::Resolution,
+        Setting::Bloom,
+        Setting::SmoothMovement,
+        Setting::SegmentStyle,
+    ];
+
+    /// Whether changing it resizes the window, which would leave a game in
+    /// progress with an arena that no longer fits.
+    fn resizes_window(self) -> bool {
+        matches!(self, Setting::Fullscreen | Setting::Resolution)
+    }
+}
+
+/// How snake segments are drawn.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SegmentStyle {
+    /// Distinct head, tapered tail and rounded corners.
+    #[default]
+    Shaped,
+    /// Every segment is a plain square.
+    Flat,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+    /// Percent, scaling every sound.
+    pub master_volume: u8,
+    /// Percent, scaling sound effects on top of the master volume.
+    pub sfx_volume: u8,
+    /// Pans eating and crashing towards where they happen.
+    pub spatial_audio: bool,
+    pub fullscreen: bool,
+    /// The window size when not fullscreen.
+    pub resolution: UVec2,
+    /// Renders in HDR with bloom, which makes the snake glow.
+    pub bloom: bool,
+    /// Segments glide between cells instead of jumping once per tick.
+    pub smooth_movement: bool,
+    pub segment_style: SegmentStyle,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            master_volume: 100,
+            sfx_volume: 100,
+            spatial_audio: true,
+            fullscreen: false,
+            resolution: RESOLUTIONS[1],
+            bloom: true,
+            smooth_movement: false,
+            segment_style: SegmentStyle::Shaped,
+        }
+    }
+}
+
+impl GameOptions {
+    /// Steps a setting up, or down when `up` is false. Volumes stop at silent
+    /// and full, while resolutions go round from the largest to the smallest.
+    fn adjust(&mut self, setting: Setting, up: bool) {
+        let step_volume = |volume: u8| {
+            if up {
+                volume.saturating_add(VOLUME_STEP).min(100)
+            } else {
+                volume.saturating_sub(VOLUME_STEP)
+            }
+        };
+        match setting {
+            Setting::MasterVolume => self.master_volume = step_volume(self.master_volume),
+            Setting::SfxVolume => self.sfx_volume = step_volume(self.sfx_volume),
+            Setting::SpatialAudio => self.spatial_audio = !self.spatial_audio,
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::Resolution => {
+                let step = if up { 1 } else { RESOLUTIONS.len() - 1 };
+                let next = RESOLUTIONS
+                    .iter()
+                    .position(|&size| size == self.resolution)
+                    .map_or(0, |index| (index + step) % RESOLUTIONS.len());
+                self.resolution = RESOLUTIONS[next];
+            }
+            Setting::Bloom => self.bloom = !self.bloom,
+            Setting::SmoothMovement => self.smooth_movement = !self.smooth_movement,
+            Setting::SegmentStyle => {
+                self.segment_style = match self.segment_style {
+                    SegmentStyle::Shaped => SegmentStyle::Flat,
+                    SegmentStyle::Flat => SegmentStyle::Shaped,
+                }
+            }
+        }
+    }
+
+    fn label(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match setting {
+            Setting::MasterVolume => format!("Master volume: {}", volume_bar(self.master_volume)),
+            Setting::SfxVolume => format!("Effects volume: {}", volume_bar(self.sfx_volume)),
+            Setting::SpatialAudio => format!("Positional sound: {}", on_off(self.spatial_audio)),
+            Setting::Fullscreen => format!("Fullscreen: {}", on_off(self.fullscreen)),
+            Setting::Resolution => {
+                format!("Window size: {}x{}", self.resolution.x, self.resolution.y)
+            }
+            Setting::Bloom => format!("Bloom: {}", on_off(self.bloom)),
+            Setting::SmoothMovement => {
+                format!("Smooth movement: {}", on_off(self.smooth_movement))
+            }
+            Setting::SegmentStyle => match self.segment_style {
+                SegmentStyle::Shaped => "Snake: Shaped".into(),
+                SegmentStyle::Flat => "Snake: Flat squares".into(),
+            },
+        }
+    }
+}
+
+fn volume_bar(percent: u8) -> String {
+    let filled = usize::from(percent.min(100) / VOLUME_STEP);
+    let empty = usize::from(100 / VOLUME_STEP) - filled;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(empty))
+}
+
+/// A one-shot sound effect at the effects volume, despawned once it has
+/// played. The master volume is applied on top through [`GlobalVolume`].
+pub fn sound_effect(sound: &Handle<AudioSource>, options: &GameOptions) -> impl Bundle {
+    (AudioPlayer(sound.clone()), sound_effect_settings(options))
+}
+
+/// Like [`sound_effect`], but heard from `position` in the world relative to
+/// the camera, unless positional sound is turned off, and played `pitch`
+/// times as fast.
+pub fn positional_sound_effect(
+    sound: &Handle<AudioSource>,
+    options: &GameOptions,
+    position: Vec2,
+    pitch: f32,
+) -> impl Bundle {
+    (
+        AudioPlayer(sound.clone()),
+        sound_effect_settings(options)
+            .with_spatial(options.spatial_audio)
+            .with_speed(pitch),
+        Transform::from_translation(position.extend(0.)),
+    )
+}
+
+fn sound_effect_settings(options: &GameOptions) -> PlaybackSettings {
+    PlaybackSettings::DESPAWN.with_volume(Volume::Linear(f32::from(options.sfx_volume) / 100.))
+}
+
+/// The screen the options screen was opened from, and goes back to.
+#[derive(Resource)]
+pub struct OptionsReturn(pub GameState);
+
+/// Lays out the options screen. Window settings are left off it when a game
+/// is paused underneath.
+pub fn setup_options(
+    mut commands: Commands,
+    theme: Res<CurrentTheme>,
+    options: Res<GameOptions>,
+    options_return: Res<OptionsReturn>,
+) {
+    let paused = options_return.0 == GameState::Pause;
+    let mut spec = MenuSpec::new(Some("Options"));
+    for setting in Setting::ALL {
+        if paused && setting.resizes_window() {
+            continue;
+        }
+        spec = spec.item(options.label(setting), MenuAction::Adjust(setting));
+    }
+    spec.item("Back", MenuAction::Back)
+        .spawn(&mut commands, &theme);
+}
+
+/// Clicking a setting, pressing Confirm on it or Right steps it up. Left
+/// steps it down, and so does right-clicking it.
+pub fn options_actions(
+    mut menu_actions: EventReader<MenuAction>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut options: ResMut<GameOptions>,
+    options_return: Res<OptionsReturn>,
+    actions: Actions,
+    mouse: Res<ButtonInput<MouseButton>>,
+    items: Query<(&Interaction, &MenuItem)>,
+) {
+    for action in menu_actions.read() {
+        match *action {
+            MenuAction::Adjust(setting) => options.adjust(setting, true),
+            MenuAction::AdjustDown(setting) => options.adjust(setting, false),
+            MenuAction::Back => next_state.set(options_return.0),
+            _ => {}
+        }
+    }
+    for (interaction, item) in &items {
+        if let MenuAction::Adjust(setting) = item.0 {
+            if *interaction == Interaction::Hovered && mouse.just_pressed(MouseButton::Right) {
+                options.adjust(setting, false);
+            }
+        }
+    }
+    if actions.just_pressed(Action::Back) {
+        next_state.set(options_return.0);
+    }
+}
+
+pub fn refresh_option_labels(
+    options: Res<GameOptions>,
+    mut labels: Query<(&mut Text, &MenuItemLabel)>,
+) {
+    if options.is_changed() {
+        for setting in Setting::ALL {
+            relabel(
+                &mut labels,
+                MenuAction::Adjust(setting),
+                &options.label(setting),
+            );
+        }
+    }
+}
+
+/// Puts the options into effect when they change, including once they are
+/// first loaded. Bloom also follows the theme.
+pub fn apply_options(
+    mut commands: Commands,
+    options: Res<GameOptions>,
+    theme: Res<CurrentTheme>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut windows: Query<&mut Window>,
+    mut cameras: Query<(Entity, &mut Camera), (With<Camera2d>, Without<Minimap>)>,
+) {
+    if options.is_changed() {
+        global_volume.volume = Volume::Linear(f32::from(options.master_volume) / 100.);
+        for mut window in &mut windows {
+            if options.fullscreen {
+                window.mode = WindowMode::BorderlessFullscreen(MonitorSelection::Current);
+            } else {
+                window.mode = WindowMode::Windowed;
+                window
+                    .resolution
+                    .set(options.resolution.x as f32, options.resolution.y as f32);
+            }
+        }
+    } else if !theme.is_changed() {
+        return;
+    }
+    for (entity, mut camera) in &mut cameras {
+        camera.hdr = options.bloom;
+        if options.bloom && theme.bloom {
+            commands.entity(entity).insert(Bloom::default());
+        } else {
+            commands.entity(entity).remove::<Bloom>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_step_both_ways() {
+        let mut options = GameOptions {
+            master_volume: 90,
+            ..default()
+        };
+        options.adjust(Setting::MasterVolume, true);
+        assert_eq!(options.master_volume, 100);
+        assert_eq!(
+            options.label(Setting::MasterVolume),
+            "Master volume: [##########]"
+        );
+        // Full stays full, rather than going round to silent.
+        options.adjust(Setting::MasterVolume, true);
+        assert_eq!(options.master_volume, 100);
+        for _ in 0..11 {
+            options.adjust(Setting::MasterVolume, false);
+        }
+        assert_eq!(options.master_volume, 0);
+        assert_eq!(
+            options.label(Setting::MasterVolume),
+            "Master volume: [----------]"
+        );
+
+        for &size in RESOLUTIONS[2..].iter().chain(&RESOLUTIONS[..2]) {
+            options.adjust(Setting::Resolution, true);
+            assert_eq!(options.resolution, size);
+        }
+        options.adjust(Setting::Resolution, false);
+        assert_eq!(options.resolution, RESOLUTIONS[0]);
+        options.adjust(Setting::Resolution, false);
+        assert_eq!(options.resolution, RESOLUTIONS[3]);
+        // A size edited into the config file by hand starts over.
+        options.resolution = UVec2::new(123, 456);
+        options.adjust(Setting::Resolution, true);
+        assert_eq!(options.resolution, RESOLUTIONS[0]);
+    }
+}
@@ -0,0 +1,152 @@
+//! Optional steering by swiping a touch screen, or by dragging or clicking
+//! with the mouse.
+use bevy::{platform::collections::HashMap, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Minimap;
+use crate::input::{snap, Action};
+use crate::{cell_to_world, Direction, PlayerControlled, Segments};
+
+/// How far, in logical pixels, a finger or the mouse has to travel to steer.
+const SWIPE_DISTANCE: f32 = 30.0;
+
+/// Whether swipes, drags and clicks steer the snake.
+#[derive(Resource, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PointerSteering(pub bool);
+
+/// Where each touch, and the mouse, last steered from.
+#[derive(Default)]
+pub struct SwipeAnchors {
+    touches: HashMap<u64, Vec2>,
+    /// The anchor, and whether the mouse has been dragged since it went down.
+    mouse: Option<(Vec2, bool)>,
+}
+
+/// Screen space has y pointing down.
+fn swipe(delta: Vec2) -> Option<Action> {
+    snap(Vec2::new(delta.x, -delta.y), SWIPE_DISTANCE)
+}
+
+/// Turns every player snake when a touch or mouse drag travels far enough,
+/// and when the mouse is clicked to one side of a snake's head.
+pub fn pointer_direction(
+    steering: Res<PointerSteering>,
+    touches: Res<Touches>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), Without<Minimap>>,
+    mut anchors: Local<SwipeAnchors>,
+    mut snakes: Query<(&mut Direction, &Segments), With<PlayerControlled>>,
+) {
+    if !steering.0 {
+        return;
+    }
+    let mut turns = Vec::new();
+
+    for touch in touches.iter_just_pressed() {
+        anchors.touches.insert(touch.id(), touch.position());
+    }
+    for touch in touches.iter() {
+        if let Some(anchor) = anchors.touches.get_mut(&touch.id()) {
+            if let Some(turn) = swipe(touch.position() - *anchor) {
+                turns.push(turn);
+                *anchor = touch.position();
+            }
+        }
+    }
+    for touch in touches
+        .iter_just_released()
+        .chain(touches.iter_just_canceled())
+    {
+        anchors.touches.remove(&touch.id());
+    }
+
+    let cursor = windows.iter().find_map(Window::cursor_position);
+    if mouse.just_pressed(MouseButton::Left) {
+        anchors.mouse = cursor.map(|cursor| (cursor, false));
+    }
+    let mut click = None;
+    if let (Some((anchor, dragged)), Some(cursor)) = (&mut anchors.mouse, cursor) {
+        let turn = swipe(cursor - *anchor).filter(|_| mouse.pressed(MouseButton::Left));
+        if let Some(turn) = turn {
+            turns.push(turn);
+            *anchor = cursor;
+            *dragged = true;
+        }
+    }
+    if mouse.just_released(MouseButton::Left) {
+        // Only a click that started in game, so the click that closed a menu
+        // doesn't steer.
+        if let Some((_, false)) = anchors.mouse.take() {
+            click = cursor.and_then(|cursor| {
+                let (camera, transform) = cameras.iter().next()?;
+                camera.viewport_to_world_2d(transform, cursor).ok()
+            });
+        }
+    }
+
+    for (mut dir, segments) in &mut snakes {
+        for &turn in &turns {
+            dir.turn(turn);
+        }
+        if let Some(click) = click {
+            // Turn towards whichever side of the head was clicked.
+            let offset = click - cell_to_world(segments.head_cell());
+            let turn = if dir.to_offset().x == 0 {
+                snap(Vec2::new(offset.x, 0.), 1.)
+            } else {
+                snap(Vec2::new(0., offset.y), 1.)
+            };
+            if let Some(turn) = turn {
+                dir.turn(turn);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::{touch::TouchPhase, InputPlugin};
+
+    fn touch(app: &mut App, phase: TouchPhase, x: f32, y: f32) {
+        app.world_mut().send_event(TouchInput {
+            phase,
+            position: Vec2::new(x, y),
+            window: Entity::PLACEHOLDER,
+            force: None,
+            id: 0,
+        });
+        app.update();
+    }
+
+    #[test]
+    fn swipes_steer_without_reversing() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .insert_resource(PointerSteering(true))
+            .add_systems(Update, pointer_direction);
+        let snake = app
+            .world_mut()
+            .spawn((
+                Direction::North,
+                PlayerControlled,
+                Segments::new(Entity::PLACEHOLDER, IVec2::ZERO),
+            ))
+            .id();
+        let heading = |app: &App, dir: Direction| app.world().get::<Direction>(snake) == Some(&dir);
+
+        touch(&mut app, TouchPhase::Started, 100., 100.);
+        // Too short to count.
+        touch(&mut app, TouchPhase::Moved, 110., 100.);
+        assert!(heading(&app, Direction::North));
+        touch(&mut app, TouchPhase::Moved, 140., 100.);
+        assert!(heading(&app, Direction::East));
+        // Screen space points down, and a drag keeps steering from where it last turned.
+        touch(&mut app, TouchPhase::Moved, 140., 140.);
+        assert!(heading(&app, Direction::South));
+        touch(&mut app, TouchPhase::Moved, 140., 100.);
+        assert!(heading(&app, Direction::South));
+        touch(&mut app, TouchPhase::Ended, 140., 100.);
+    }
+}
@@ -0,0 +1,495 @@
+//! Power-ups: pickups that give the snake a timed effect. Each effect is a
+//! component on the snake entity that is removed when its time runs out.
+//!
+//! Picking up an effect the snake already has adds its duration to what is
+//! left, up to `MAX_STACK` times the duration. Different effects are
+//! independent and can all be active at once. A shield only ever saves the
+//! snake from one wall, however many are stacked.
+use bevy::{ecs::component::Mutable, prelude::*};
+
+use crate::menu::MenuText;
+use crate::occupancy::Occupancy;
+use crate::options::{positional_sound_effect, GameOptions};
+use crate::theme::CurrentTheme;
+use crate::{
+    cell_to_world, CleanupOnRestart, EatSound, FoodKind, GameRng, GridPosition, MoveTimer,
+    PlayerControlled, Segments, SnakeAssets, MOVE_SECS,
+};
+
+/// How long a pickup waits on the board before vanishing.
+const PICKUP_LIFETIME_SECS: f32 = 10.;
+const PICKUP_SPAWN_SECS: f32 = 7.;
+/// The most time an effect can have left, in multiples of its duration.
+const MAX_STACK: f32 = 2.;
+/// How many cells away the magnet reaches, in either direction.
+const MAGNET_RADIUS: i32 = 6;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PowerUpKind {
+    Ghost,
+    Shield,
+    SlowMotion,
+    Magnet,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Ghost,
+        PowerUpKind::Shield,
+        PowerUpKind::SlowMotion,
+        PowerUpKind::Magnet,
+    ];
+
+    fn duration_secs(self) -> f32 {
+        match self {
+            PowerUpKind::Ghost => 6.,
+            PowerUpKind::Shield => 15.,
+            PowerUpKind::SlowMotion => 5.,
+            PowerUpKind::Magnet => 8.,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PowerUpKind::Ghost => "Ghost",
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::SlowMotion => "Slow",
+            PowerUpKind::Magnet => "Magnet",
+        }
+    }
+
+    /// Pickups and their HUD icons share a color, which glows with bloom.
+    fn color(self) -> Color {
+        match self {
+            PowerUpKind::Ghost => Color::srgb(1.5, 1.5, 2.0),
+            PowerUpKind::Shield => Color::srgb(2.0, 1.6, 0.2),
+            PowerUpKind::SlowMotion => Color::srgb(0.8, 0.3, 2.0),
+            PowerUpKind::Magnet => Color::srgb(2.0, 0.2, 0.3),
+        }
+    }
+}
+
+/// A timed effect on a snake.
+pub trait Effect: Component<Mutability = Mutable> {
+    const KIND: PowerUpKind;
+    fn new(timer: Timer) -> Self;
+    fn timer(&self) -> &Timer;
+    fn timer_mut(&mut self) -> &mut Timer;
+}
+
+macro_rules! effect {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Component)]
+        pub struct $name(Timer);
+
+        impl Effect for $name {
+            const KIND: PowerUpKind = PowerUpKind::$name;
+            fn new(timer: Timer) -> Self {
+                $name(timer)
+            }
+            fn timer(&self) -> &Timer {
+                &self.0
+            }
+            fn timer_mut(&mut self) -> &mut Timer {
+                &mut self.0
+            }
+        }
+    };
+}
+
+effect!(
+    /// The snake passes through its own body.
+    Ghost
+);
+effect!(
+    /// The next wall the snake hits breaks the shield instead of the snake,
+    /// which comes out at the opposite wall.
+    Shield
+);
+effect!(
+    /// The snake moves at half speed.
+    SlowMotion
+);
+effect!(
+    /// Food nearby creeps towards the head.
+    Magnet
+);
+
+/// `T`, with its duration added to any time left on `existing`.
+fn stacked<T: Effect>(existing: Option<&T>) -> T {
+    let duration = T::KIND.duration_secs();
+    let left = existing.map_or(0., |effect| effect.timer().remaining_secs());
+    T::new(Timer::from_seconds(
+        (left + duration).min(duration * MAX_STACK),
+        TimerMode::Once,
+    ))
+}
+
+fn remaining<T: Effect>(effect: Option<&T>) -> Option<f32> {
+    effect.map(|effect| effect.timer().remaining_secs())
+}
+
+/// A power-up waiting on the board to be picked up.
+#[derive(Component)]
+pub struct PowerUp {
+    kind: PowerUpKind,
+    lifetime: Timer,
+}
+
+#[derive(Resource)]
+pub struct PowerUpAssets {
+    materials: Vec<Handle<ColorMaterial>>,
+}
+
+pub fn setup_power_up_assets(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands.insert_resource(PowerUpAssets {
+        materials: PowerUpKind::ALL
+            .iter()
+            .map(|kind| materials.add(kind.color()))
+            .collect(),
+    });
+}
+
+#[derive(Resource)]
+pub struct PowerUpSpawnTimer(Timer);
+
+impl Default for PowerUpSpawnTimer {
+    fn default() -> Self {
+        PowerUpSpawnTimer(Timer::from_seconds(PICKUP_SPAWN_SECS, TimerMode::Repeating))
+    }
+}
+
+/// Places a random power-up on a free cell now and then, one at a time.
+pub fn spawn_power_ups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<PowerUpSpawnTimer>,
+    mut rng: ResMut<GameRng>,
+    mut occupancy: ResMut<Occupancy>,
+    snake_assets: Res<SnakeAssets>,
+    power_up_assets: Res<PowerUpAssets>,
+    pickups: Query<(), With<PowerUp>>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    use rand::Rng;
+    let rng = &mut rng.power_ups;
+    let (min, max) = occupancy.bounds();
+    // Drawn even when nothing is placed, so the sequence stays the same.
+    let cell = IVec2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
+    let index = rng.gen_range(0..PowerUpKind::ALL.len());
+    if !pickups.is_empty() || !occupancy.is_free(cell) {
+        return;
+    }
+    let pos = cell_to_world(cell);
+    let pickup = commands
+        .spawn((
+            Name::new("power-up"),
+            PowerUp {
+                kind: PowerUpKind::ALL[index],
+                lifetime: Timer::from_seconds(PICKUP_LIFETIME_SECS, TimerMode::Once),
+            },
+            CleanupOnRestart,
+            GridPosition(cell),
+            // Turned on its corner so it doesn't look like food.
+            Transform::from_xyz(pos.x, pos.y, 0.)
+                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+            Mesh2d(snake_assets.straight.clone()),
+            MeshMaterial2d(power_up_assets.materials[index].clone()),
+        ))
+        .id();
+    occupancy.set_power_up(cell, Some(pickup));
+}
+
+/// Removes pickups that have waited too long.
+pub fn expire_power_ups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut occupancy: ResMut<Occupancy>,
+    mut pickups: Query<(Entity, &mut PowerUp, &GridPosition)>,
+) {
+    for (entity, mut pickup, position) in &mut pickups {
+        if pickup.lifetime.tick(time.delta()).just_finished() {
+            occupancy.set_power_up(position.0, None);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Gives a snake the effect of any pickup its head is on.
+pub fn collect_power_ups(
+    mut commands: Commands,
+    mut occupancy: ResMut<Occupancy>,
+    eat_sound: Res<EatSound>,
+    options: Res<GameOptions>,
+    pickups: Query<&PowerUp>,
+    snakes: Query<
+        (
+            Entity,
+            &Segments,
+            Option<&Ghost>,
+            Option<&Shield>,
+            Option<&SlowMotion>,
+            Option<&Magnet>,
+        ),
+        With<PlayerControlled>,
+    >,
+) {
+    for (snake, segments, ghost, shield, slow_motion, magnet) in &snakes {
+        let head = segments.head_cell();
+        let Some(pickup) = occupancy.power_up_at(head) else {
+            continue;
+        };
+        occupancy.set_power_up(head, None);
+        commands.entity(pickup).despawn();
+        let Ok(power_up) = pickups.get(pickup) else {
+            continue;
+        };
+        let mut snake = commands.entity(snake);
+        match power_up.kind {
+            PowerUpKind::Ghost => snake.insert(stacked(ghost)),
+            PowerUpKind::Shield => snake.insert(stacked(shield)),
+            PowerUpKind::SlowMotion => snake.insert(stacked(slow_motion)),
+            PowerUpKind::Magnet => snake.insert(stacked(magnet)),
+        };
+        commands.spawn(positional_sound_effect(
+            eat_sound.for_kind(FoodKind::Bonus),
+            &options,
+            cell_to_world(head),
+            0.5,
+        ));
+    }
+}
+
+/// Counts down an effect and takes it away when it runs out.
+pub fn tick_effect<T: Effect>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut T)>,
+) {
+    for (entity, mut effect) in &mut effects {
+        if effect.timer_mut().tick(time.delta()).finished() {
+            commands.entity(entity).remove::<T>();
+        }
+    }
+}
+
+/// Moves at half speed while any snake has slow motion.
+pub fn apply_slow_motion(mut timer: ResMut<MoveTimer>, slowed: Query<(), With<SlowMotion>>) {
+    let secs = if slowed.is_empty() {
+        MOVE_SECS
+    } else {
+        MOVE_SECS * 2.
+    };
+    if timer.0.duration().as_secs_f32() != secs {
+        timer
+            .0
+            .set_duration(std::time::Duration::from_secs_f32(secs));
+    }
+}
+
+/// Each move, pulls food within reach of a magnetised head one cell closer.
+pub fn pull_food(
+    timer: Res<MoveTimer>,
+    mut occupancy: ResMut<Occupancy>,
+    snakes: Query<&Segments, With<Magnet>>,
+    mut food: Query<(Entity, &mut GridPosition, &mut Transform), With<FoodKind>>,
+) {
+    if !timer.0.just_finished() {
+        return;
+    }
+    for segments in &snakes {
+        let head = segments.head_cell();
+        for (entity, mut position, mut transform) in &mut food {
+            let offset = head - position.0;
+            if offset == IVec2::ZERO || offset.abs().max_element() > MAGNET_RADIUS {
+                continue;
+            }
+            // Along whichever axis it is further away on.
+            let step = if offset.x.abs() >= offset.y.abs() {
+                IVec2::new(offset.x.signum(), 0)
+            } else {
+                IVec2::new(0, offset.y.signum())
+            };
+            let next = position.0 + step;
+            // Food may move onto the head, to be eaten, but not anything
+            // else. Only one piece fits there at a time.
+            let blocked = if next == head {
+                occupancy.food_at(head).is_some()
+            } else {
+                !occupancy.is_free(next)
+            };
+            if blocked {
+                continue;
+            }
+            occupancy.set_food(position.0, None);
+            occupancy.set_food(next, Some(entity));
+            position.0 = next;
+            let pos = cell_to_world(next);
+            transform.translation.x = pos.x;
+            transform.translation.y = pos.y;
+        }
+    }
+}
+
+/// A row of the HUD showing an effect and how long it has left.
+#[derive(Component)]
+pub struct EffectRow(PowerUpKind);
+
+#[derive(Component)]
+pub struct EffectCountdown(PowerUpKind);
+
+pub fn setup_effect_hud(mut commands: Commands, theme: Res<CurrentTheme>) {
+    commands
+        .spawn((
+            Name::new("effects hud"),
+            CleanupOnRestart,
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                right: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::End,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for kind in PowerUpKind::ALL {
+                parent
+                    .spawn((
+                        EffectRow(kind),
+                        Node {
+                            display: Display::None,
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(8.),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            EffectCountdown(kind),
+                            MenuText,
+                            Text::new(""),
+                            theme.font.clone(),
+                            TextColor(theme.text),
+                        ));
+                        parent.spawn((
+                            Node {
+                                width: Val::Px(16.),
+                                height: Val::Px(16.),
+                                ..default()
+                            },
+                            BackgroundColor(kind.color()),
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn update_effect_hud(
+    snakes: Query<
+        (
+            Option<&Ghost>,
+            Option<&Shield>,
+            Option<&SlowMotion>,
+            Option<&Magnet>,
+        ),
+        With<PlayerControlled>,
+    >,
+    mut rows: Query<(&mut Node, &EffectRow)>,
+    mut countdowns: Query<(&mut Text, &EffectCountdown)>,
+) {
+    let left = |kind: PowerUpKind| {
+        snakes
+            .iter()
+            .filter_map(|(ghost, shield, slow_motion, magnet)| match kind {
+                PowerUpKind::Ghost => remaining(ghost),
+                PowerUpKind::Shield => remaining(shield),
+                PowerUpKind::SlowMotion => remaining(slow_motion),
+                PowerUpKind::Magnet => remaining(magnet),
+            })
+            .reduce(f32::max)
+    };
+    for (mut node, row) in &mut rows {
+        let display = if left(row.0).is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if node.display != display {
+            node.display = display;
+        }
+    }
+    for (mut text, countdown) in &mut countdowns {
+        if let Some(secs) = left(countdown.0) {
+            let label = format!("{} {secs:.1}", countdown.0.label());
+            if text.0 != label {
+                text.0 = label;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    #[test]
+    fn magnet_pulls_one_piece_of_food_onto_the_head_at_a_time() {
+        let mut world = World::new();
+        let mut timer = Timer::from_seconds(MOVE_SECS, TimerMode::Repeating);
+        timer.tick(Duration::from_secs_f32(MOVE_SECS));
+        world.insert_resource(MoveTimer(timer));
+        let mut occupancy = Occupancy::new(IVec2::splat(-5), IVec2::splat(5));
+        let head = world.spawn_empty().id();
+        world.spawn((
+            Segments::new(head, IVec2::ZERO),
+            Magnet::new(Timer::default()),
+        ));
+        occupancy.add_segment(IVec2::ZERO);
+        let mut food = |world: &mut World, cell: IVec2| {
+            let food = world
+                .spawn((FoodKind::Regular, GridPosition(cell), Transform::default()))
+                .id();
+            occupancy.set_food(cell, Some(food));
+            (food, cell)
+        };
+        // Either side of the head.
+        let pieces = [food(&mut world, IVec2::NEG_X), food(&mut world, IVec2::X)];
+        world.insert_resource(occupancy);
+        world.run_system_once(pull_food).unwrap();
+
+        let occupancy = world.resource::<Occupancy>();
+        let on_head = occupancy.food_at(IVec2::ZERO).unwrap();
+        for (food, cell) in pieces {
+            let at = world.get::<GridPosition>(food).unwrap().0;
+            if food == on_head {
+                assert_eq!(at, IVec2::ZERO);
+            } else {
+                // Left where it was, and still on the grid to be eaten.
+                assert_eq!(at, cell);
+                assert_eq!(occupancy.food_at(cell), Some(food));
+            }
+        }
+    }
+
+    #[test]
+    fn picking_up_an_effect_again_adds_time_up_to_a_cap() {
+        let duration = PowerUpKind::Ghost.duration_secs();
+        let ghost: Ghost = stacked(None);
+        assert_eq!(ghost.timer().remaining_secs(), duration);
+
+        let mut ghost = ghost;
+        ghost
+            .timer_mut()
+            .tick(std::time::Duration::from_secs_f32(duration / 2.));
+        let ghost = stacked(Some(&ghost));
+        assert_eq!(ghost.timer().remaining_secs(), duration * 1.5);
+        let ghost = stacked(Some(&ghost));
+        assert_eq!(ghost.timer().remaining_secs(), duration * MAX_STACK);
+    }
+}
@@ -0,0 +1,89 @@
+//! Game data written as RON assets, like themes and levels, and the lists of
+//! them the menu cycles through.
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+/// An asset read straight from a RON file.
+pub trait RonAsset: Asset + DeserializeOwned {
+    /// The file extensions it is loaded from, such as `theme.ron`.
+    const EXTENSIONS: &'static [&'static str];
+}
+
+/// Optional fields can be written without `Some(..)`.
+pub fn parse<A: RonAsset>(bytes: &[u8]) -> ron::error::SpannedResult<A> {
+    ron::Options::default()
+        .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+        .from_bytes(bytes)
+}
+
+pub struct RonLoader<A>(PhantomData<fn() -> A>);
+
+impl<A> Default for RonLoader<A> {
+    fn default() -> Self {
+        RonLoader(PhantomData)
+    }
+}
+
+impl<A: RonAsset> AssetLoader for RonLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(parse(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        A::EXTENSIONS
+    }
+}
+
+/// Assets the menu picks one of, cycling through them in the order they were
+/// loaded.
+#[derive(Resource)]
+pub struct AssetList<A: Asset> {
+    handles: Vec<Handle<A>>,
+    selected: usize,
+}
+
+impl<A: Asset> AssetList<A> {
+    /// Starts loading the assets at `paths`, with the first one selected.
+    pub fn load(server: &AssetServer, paths: &[&'static str]) -> Self {
+        AssetList {
+            handles: paths.iter().map(|path| server.load(*path)).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        self.selected = (self.selected + 1) % self.handles.len();
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index % self.handles.len();
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn count(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn selected(&self) -> &Handle<A> {
+        &self.handles[self.selected]
+    }
+}
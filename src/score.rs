@@ -0,0 +1,236 @@
+//! Scoring, with a combo multiplier for eating food in quick succession, and
+//! the HUD that shows both.
+use bevy::prelude::*;
+
+use crate::menu::MenuText;
+use crate::theme::CurrentTheme;
+use crate::{CleanupOnRestart, FoodCollisionEvent, MoveTimer, FOOD_POINTS};
+
+/// Ticks after eating in which the next piece of food raises the combo.
+const COMBO_WINDOW_TICKS: u32 = 20;
+const MAX_COMBO: u32 = 8;
+/// How far each step of the combo raises the eating sound, in semitones.
+const COMBO_PITCH_STEP: f32 = 2.;
+
+#[derive(Resource, Default)]
+pub struct Score(pub u32);
+
+/// The score multiplier. It goes up by one for each piece of food eaten
+/// within the window after the last, and back down by one each time the
+/// window runs out.
+#[derive(Resource)]
+pub struct Combo {
+    multiplier: u32,
+    /// Ticks left in the window, shown by the combo bar.
+    ticks_left: u32,
+    /// Once the window has run out, ticks until the next step down.
+    decay_ticks: u32,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Combo {
+            multiplier: 1,
+            ticks_left: 0,
+            decay_ticks: 0,
+        }
+    }
+}
+
+impl Combo {
+    /// How fast to play the eating sound, so it rises with the combo.
+    pub fn pitch(&self) -> f32 {
+        2f32.powf((self.multiplier - 1) as f32 * COMBO_PITCH_STEP / 12.)
+    }
+
+    /// How much of the window is left, from 0 to 1.
+    fn remaining(&self) -> f32 {
+        self.ticks_left as f32 / COMBO_WINDOW_TICKS as f32
+    }
+
+    fn tick(&mut self) {
+        if self.ticks_left > 0 {
+            self.ticks_left -= 1;
+            if self.ticks_left == 0 {
+                self.step_down();
+            }
+        } else if self.decay_ticks > 0 {
+            self.decay_ticks -= 1;
+            if self.decay_ticks == 0 {
+                self.step_down();
+            }
+        }
+    }
+
+    /// Takes one off the multiplier, and keeps decaying a step at a time
+    /// rather than dropping to nothing. The bar stays empty meanwhile.
+    fn step_down(&mut self) {
+        self.multiplier = (self.multiplier - 1).max(1);
+        if self.multiplier > 1 {
+            self.decay_ticks = COMBO_WINDOW_TICKS;
+        }
+    }
+
+    fn eat(&mut self) {
+        if self.ticks_left > 0 {
+            self.multiplier = (self.multiplier + 1).min(MAX_COMBO);
+        }
+        self.ticks_left = COMBO_WINDOW_TICKS;
+        self.decay_ticks = 0;
+    }
+}
+
+pub fn reset_score(mut commands: Commands) {
+    commands.insert_resource(Score::default());
+    commands.insert_resource(Combo::default());
+}
+
+/// Counts down the combo window on every move and scores the food eaten.
+/// Runs after the snake has moved and eaten for the tick.
+pub fn update_combo(
+    timer: Res<MoveTimer>,
+    mut food_collision_reader: EventReader<FoodCollisionEvent>,
+    mut combo: ResMut<Combo>,
+    mut score: ResMut<Score>,
+) {
+    if timer.0.just_finished() {
+        combo.tick();
+    }
+    for _ in food_collision_reader.read() {
+        combo.eat();
+        score.0 += FOOD_POINTS * combo.multiplier;
+    }
+}
+
+#[derive(Component)]
+pub struct ScoreText;
+
+#[derive(Component)]
+pub struct ComboText;
+
+/// The part of the combo bar that shrinks as the window runs out.
+#[derive(Component)]
+pub struct ComboBar;
+
+pub fn setup_hud(mut commands: Commands, theme: Res<CurrentTheme>) {
+    commands
+        .spawn((
+            Name::new("hud"),
+            CleanupOnRestart,
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                left: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ScoreText,
+                MenuText,
+                Text::new("Score: 0"),
+                theme.font.clone(),
+                TextColor(theme.text),
+            ));
+            parent
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        ComboText,
+                        MenuText,
+                        Text::new("x1"),
+                        theme.font.clone(),
+                        TextColor(theme.text),
+                    ));
+                    parent
+                        .spawn((
+                            Node {
+                                width: Val::Px(120.),
+                                height: Val::Px(8.),
+                                ..default()
+                            },
+                            BackgroundColor(theme.normal_button),
+                        ))
+                        .with_child((
+                            ComboBar,
+                            Node {
+                                width: Val::Percent(0.),
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            BackgroundColor(theme.food),
+                        ));
+                });
+        });
+}
+
+pub fn update_hud(
+    score: Res<Score>,
+    combo: Res<Combo>,
+    theme: Res<CurrentTheme>,
+    mut score_text: Query<&mut Text, (With<ScoreText>, Without<ComboText>)>,
+    mut combo_text: Query<&mut Text, (With<ComboText>, Without<ScoreText>)>,
+    mut bar: Query<(&mut Node, &mut BackgroundColor), With<ComboBar>>,
+) {
+    if score.is_changed() {
+        for mut text in &mut score_text {
+            text.0 = format!("Score: {}", score.0);
+        }
+    }
+    if combo.is_changed() {
+        for mut text in &mut combo_text {
+            text.0 = format!("x{}", combo.multiplier);
+        }
+    }
+    for (mut node, mut color) in &mut bar {
+        node.width = Val::Percent(combo.remaining() * 100.);
+        color.set_if_neq(BackgroundColor(theme.food));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combo_builds_within_the_window_and_decays_after() {
+        let mut combo = Combo::default();
+        combo.eat();
+        assert_eq!(combo.multiplier, 1);
+        for _ in 0..COMBO_WINDOW_TICKS - 1 {
+            combo.tick();
+        }
+        combo.eat();
+        combo.eat();
+        assert_eq!(combo.multiplier, 3);
+        assert_eq!(combo.pitch(), 2f32.powf(4. / 12.));
+
+        // Each lapsed window takes one step off, without refilling the bar.
+        for _ in 0..COMBO_WINDOW_TICKS {
+            combo.tick();
+        }
+        assert_eq!(combo.multiplier, 2);
+        for _ in 0..COMBO_WINDOW_TICKS / 2 {
+            combo.tick();
+            assert_eq!(combo.remaining(), 0.);
+        }
+        for _ in 0..COMBO_WINDOW_TICKS / 2 {
+            combo.tick();
+        }
+        assert_eq!(combo.multiplier, 1);
+        assert_eq!(combo.remaining(), 0.);
+
+        // Eating again only starts a new window.
+        combo.eat();
+        assert_eq!(combo.multiplier, 1);
+        for _ in 0..MAX_COMBO + 2 {
+            combo.eat();
+        }
+        assert_eq!(combo.multiplier, MAX_COMBO);
+    }
+}
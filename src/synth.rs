@@ -0,0 +1,263 @@
+//! A small synthesizer that renders the music and sound effects at startup,
+//! so the game has sounds without any audio files. Themes can still point at
+//! `.wav` files to replace the sound effects.
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::FoodKind;
+
+const SAMPLE_RATE: u32 = 22050;
+
+#[derive(Clone, Copy)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Noise,
+}
+
+/// Attack, decay and release times in seconds, and the level held in between
+/// as a fraction of the peak.
+#[derive(Clone, Copy)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    const PLUCK: Envelope = Envelope {
+        attack: 0.005,
+        decay: 0.04,
+        sustain: 0.5,
+        release: 0.03,
+    };
+    const HIT: Envelope = Envelope {
+        attack: 0.002,
+        decay: 0.15,
+        sustain: 0.3,
+        release: 0.2,
+    };
+
+    /// The level `t` seconds into a note lasting `duration` seconds.
+    fn level(&self, t: f32, duration: f32) -> f32 {
+        let held = if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.decay {
+            1. - (1. - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        };
+        let release = ((duration - t) / self.release).clamp(0., 1.);
+        held * release
+    }
+}
+
+/// A note that slides from one frequency to another over its length.
+#[derive(Clone, Copy)]
+pub struct Tone {
+    start: f32,
+    duration: f32,
+    waveform: Waveform,
+    from_hz: f32,
+    to_hz: f32,
+    envelope: Envelope,
+    volume: f32,
+}
+
+impl Tone {
+    pub fn new(waveform: Waveform, hz: f32, start: f32, duration: f32) -> Self {
+        Tone {
+            start,
+            duration,
+            waveform,
+            from_hz: hz,
+            to_hz: hz,
+            envelope: Envelope::PLUCK,
+            volume: 0.5,
+        }
+    }
+
+    pub fn slide_to(self, to_hz: f32) -> Self {
+        Tone { to_hz, ..self }
+    }
+
+    pub fn envelope(self, envelope: Envelope) -> Self {
+        Tone { envelope, ..self }
+    }
+
+    pub fn volume(self, volume: f32) -> Self {
+        Tone { volume, ..self }
+    }
+}
+
+/// Mixes the tones into samples between -1 and 1.
+fn render(tones: &[Tone]) -> Vec<f32> {
+    let end = tones
+        .iter()
+        .map(|tone| tone.start + tone.duration)
+        .fold(0., f32::max);
+    let mut samples = vec![0.; (end * SAMPLE_RATE as f32).ceil() as usize];
+    // A fixed seed, so the noise sounds the same every run.
+    let mut noise = 0x2545_f491_u32;
+    for tone in tones {
+        let first = (tone.start * SAMPLE_RATE as f32) as usize;
+        let count = (tone.duration * SAMPLE_RATE as f32) as usize;
+        let mut phase = 0.;
+        for (i, sample) in samples[first..].iter_mut().take(count).enumerate() {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let hz = tone.from_hz + (tone.to_hz - tone.from_hz) * t / tone.duration;
+            phase = (phase + hz / SAMPLE_RATE as f32) % 1.;
+            let wave = match tone.waveform {
+                Waveform::Sine => (phase * TAU).sin(),
+                Waveform::Square => {
+                    if phase < 0.5 {
+                        1.
+                    } else {
+                        -1.
+                    }
+                }
+                Waveform::Triangle => 4. * (phase - 0.5).abs() - 1.,
+                Waveform::Noise => {
+                    noise ^= noise << 13;
+                    noise ^= noise >> 17;
+                    noise ^= noise << 5;
+                    noise as f32 / u32::MAX as f32 * 2. - 1.
+                }
+            };
+            *sample += wave * tone.volume * tone.envelope.level(t, tone.duration);
+        }
+    }
+    for sample in &mut samples {
+        *sample = sample.clamp(-1., 1.);
+    }
+    samples
+}
+
+/// Renders tones into a loop exactly `secs` long, cutting off anything that
+/// runs past the end, and scales it so its loudest sample is at `peak`.
+pub fn render_loop(tones: &[Tone], secs: f32, peak: f32) -> Vec<f32> {
+    let mut samples = render(tones);
+    samples.resize((secs * SAMPLE_RATE as f32).round() as usize, 0.);
+    let loudest = samples
+        .iter()
+        .fold(0., |loudest: f32, sample| loudest.max(sample.abs()));
+    if loudest > 0. {
+        for sample in &mut samples {
+            *sample *= peak / loudest;
+        }
+    }
+    samples
+}
+
+/// Wraps samples in a 16 bit mono WAV file, which is what [`AudioSource`]
+/// expects to decode.
+pub fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    bytes
+}
+
+/// Each kind of food chimes in its own way.
+fn eat_tones(kind: FoodKind) -> Vec<Tone> {
+    match kind {
+        FoodKind::Regular => vec![
+            Tone::new(Waveform::Square, 660., 0., 0.06).volume(0.25),
+            Tone::new(Waveform::Square, 990., 0.05, 0.08).volume(0.25),
+        ],
+        FoodKind::Bonus => [1320., 1661., 1976., 2637.]
+            .into_iter()
+            .enumerate()
+            .map(|(i, hz)| Tone::new(Waveform::Triangle, hz, i as f32 * 0.04, 0.1))
+            .collect(),
+    }
+}
+
+fn menu_rollover_tones() -> Vec<Tone> {
+    vec![Tone::new(Waveform::Sine, 1200., 0., 0.04).volume(0.3)]
+}
+
+fn crash_tones() -> Vec<Tone> {
+    vec![
+        Tone::new(Waveform::Noise, 0., 0., 0.5)
+            .envelope(Envelope::HIT)
+            .volume(0.6),
+        Tone::new(Waveform::Square, 220., 0., 0.5)
+            .slide_to(40.)
+            .envelope(Envelope::HIT)
+            .volume(0.3),
+    ]
+}
+
+fn start_tones() -> Vec<Tone> {
+    [523., 659., 784., 1047.]
+        .into_iter()
+        .enumerate()
+        .map(|(i, hz)| Tone::new(Waveform::Square, hz, i as f32 * 0.07, 0.09).volume(0.25))
+        .collect()
+}
+
+/// The synthesized sounds, used for any sound a theme doesn't override.
+#[derive(Resource)]
+pub struct SynthSounds {
+    pub eat_regular: Handle<AudioSource>,
+    pub eat_bonus: Handle<AudioSource>,
+    pub menu_rollover: Handle<AudioSource>,
+    pub crash: Handle<AudioSource>,
+    pub start: Handle<AudioSource>,
+}
+
+pub fn synthesize_sounds(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let mut add = |tones: Vec<Tone>| {
+        sources.add(AudioSource {
+            bytes: wav(&render(&tones)).into(),
+        })
+    };
+    commands.insert_resource(SynthSounds {
+        eat_regular: add(eat_tones(FoodKind::Regular)),
+        eat_bonus: add(eat_tones(FoodKind::Bonus)),
+        menu_rollover: add(menu_rollover_tones()),
+        crash: add(crash_tones()),
+        start: add(start_tones()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tones_render_to_a_wav_of_their_length() {
+        let tones = [
+            Tone::new(Waveform::Sine, 440., 0., 0.1),
+            Tone::new(Waveform::Noise, 0., 0.05, 0.15),
+        ];
+        let samples = render(&tones);
+        assert_eq!(samples.len(), (0.2 * SAMPLE_RATE as f32).ceil() as usize);
+        // Notes fade in from silence and out to it.
+        assert_eq!(samples[0], 0.);
+        assert!(samples.last().unwrap().abs() < 0.01);
+        assert!(samples.iter().any(|sample| sample.abs() > 0.2));
+
+        let bytes = wav(&samples);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(bytes.len(), 44 + samples.len() * 2);
+    }
+}
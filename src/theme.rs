@@ -0,0 +1,211 @@
+//! Colors, fonts and sounds, loaded from `.theme.ron` assets.
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ron_asset::{AssetList, RonAsset};
+use crate::synth::SynthSounds;
+use crate::{CrashSound, EatSound, MenuRolloverSound, SnakeAssets, StartSound};
+
+/// Themes that ship with the game. The first matches the fallback used until
+/// it has loaded.
+const BUILTIN_THEMES: [&str; 3] = [
+    "themes/classic.theme.ron",
+    "themes/neon.theme.ron",
+    "themes/high-contrast.theme.ron",
+];
+
+/// Colors are sRGBA components. Values above 1.0 glow when the theme
+/// turns on bloom.
+#[derive(Asset, TypePath, Deserialize, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub snake: [f32; 4],
+    pub food: [f32; 4],
+    pub highlight: [f32; 4],
+    pub background: [f32; 4],
+    pub text: [f32; 4],
+    pub buttons: ButtonStyle,
+    /// Path to a font asset. The built in font is used when this is missing.
+    #[serde(default)]
+    pub font: Option<String>,
+    pub font_size: f32,
+    #[serde(default)]
+    pub sounds: SoundSet,
+    #[serde(default)]
+    pub bloom: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ButtonStyle {
+    pub normal: [f32; 4],
+    pub hovered: [f32; 4],
+    pub pressed: [f32; 4],
+}
+
+/// Paths to sound assets that replace the synthesized sounds. Bonus food
+/// falls back to the `eat` sound when it has none of its own.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SoundSet {
+    pub eat: Option<String>,
+    pub eat_bonus: Option<String>,
+    pub menu_rollover: Option<String>,
+    pub crash: Option<String>,
+    pub start: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Classic".into(),
+            snake: [1.0, 0.0, 0.0, 1.0],
+            food: [0.1, 1.0, 0.0, 1.0],
+            highlight: [4.0, 4.0, 0.5, 1.0],
+            background: [0.169, 0.173, 0.184, 1.0],
+            text: [0.9, 0.9, 0.9, 1.0],
+            buttons: ButtonStyle {
+                normal: [0.15, 0.15, 0.15, 1.0],
+                hovered: [0.25, 0.25, 0.25, 1.0],
+                pressed: [0.35, 0.75, 0.35, 1.0],
+            },
+            font: None,
+            font_size: 33.0,
+            sounds: SoundSet::default(),
+            bloom: true,
+        }
+    }
+}
+
+fn color([r, g, b, a]: [f32; 4]) -> Color {
+    Color::srgba(r, g, b, a)
+}
+
+impl RonAsset for Theme {
+    const EXTENSIONS: &'static [&'static str] = &["theme.ron"];
+}
+
+/// The active theme, resolved into the types the rest of the game uses.
+#[derive(Resource)]
+pub struct CurrentTheme {
+    pub name: String,
+    pub snake: Color,
+    pub food: Color,
+    pub highlight: Color,
+    pub text: Color,
+    pub normal_button: Color,
+    pub hovered_button: Color,
+    pub pressed_button: Color,
+    pub font: TextFont,
+    /// Whether the theme wants bloom, which the options can still turn off.
+    pub bloom: bool,
+}
+
+impl CurrentTheme {
+    fn new(theme: &Theme, server: &AssetServer) -> Self {
+        CurrentTheme {
+            name: theme.name.clone(),
+            snake: color(theme.snake),
+            food: color(theme.food),
+            highlight: color(theme.highlight),
+            text: color(theme.text),
+            normal_button: color(theme.buttons.normal),
+            hovered_button: color(theme.buttons.hovered),
+            pressed_button: color(theme.buttons.pressed),
+            font: TextFont {
+                font: theme
+                    .font
+                    .as_ref()
+                    .map_or_else(Handle::default, |path| server.load(path)),
+                font_size: theme.font_size,
+                ..default()
+            },
+            bloom: theme.bloom,
+        }
+    }
+}
+
+pub type Themes = AssetList<Theme>;
+
+/// Starts loading the built in themes and applies the fallback until they arrive.
+pub fn load_themes(mut commands: Commands, server: Res<AssetServer>, synth: Res<SynthSounds>) {
+    let theme = Theme::default();
+    insert_sounds(&mut commands, &server, &synth, &theme.sounds);
+    commands.insert_resource(ClearColor(color(theme.background)));
+    commands.insert_resource(CurrentTheme::new(&theme, &server));
+    commands.insert_resource(Themes::load(&server, &BUILTIN_THEMES));
+}
+
+fn insert_sounds(
+    commands: &mut Commands,
+    server: &AssetServer,
+    synth: &SynthSounds,
+    sounds: &SoundSet,
+) {
+    let load = |path: Option<&String>, synthesized: &Handle<AudioSource>| {
+        path.map_or_else(|| synthesized.clone(), |path| server.load(path))
+    };
+    commands.insert_resource(EatSound {
+        regular: load(sounds.eat.as_ref(), &synth.eat_regular),
+        bonus: load(
+            sounds.eat_bonus.as_ref().or(sounds.eat.as_ref()),
+            &synth.eat_bonus,
+        ),
+    });
+    commands.insert_resource(MenuRolloverSound(load(
+        sounds.menu_rollover.as_ref(),
+        &synth.menu_rollover,
+    )));
+    commands.insert_resource(CrashSound(load(sounds.crash.as_ref(), &synth.crash)));
+    commands.insert_resource(StartSound(load(sounds.start.as_ref(), &synth.start)));
+}
+
+/// Switches to the selected theme when it changes, finishes loading, or is
+/// edited on disk.
+pub fn apply_theme(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Theme>>,
+    themes: Res<Themes>,
+    theme_assets: Res<Assets<Theme>>,
+    server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    snake_assets: Res<SnakeAssets>,
+    synth: Res<SynthSounds>,
+) {
+    let selected = themes.selected().id();
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id == selected,
+        _ => false,
+    });
+    if !reloaded && !themes.is_changed() {
+        return;
+    }
+    let Some(theme) = theme_assets.get(selected) else {
+        return;
+    };
+
+    let current = CurrentTheme::new(theme, &server);
+    snake_assets.recolor(&mut materials, &current);
+    insert_sounds(&mut commands, &server, &synth, &theme.sounds);
+    commands.insert_resource(ClearColor(color(theme.background)));
+    commands.insert_resource(current);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ron_asset::parse;
+
+    #[test]
+    fn builtin_themes_parse() {
+        for path in BUILTIN_THEMES {
+            let bytes = std::fs::read(format!("assets/{path}")).unwrap();
+            if let Err(err) = parse::<Theme>(&bytes) {
+                panic!("{path}: {err}");
+            }
+        }
+        let classic: Theme =
+            parse(&std::fs::read("assets/themes/classic.theme.ron").unwrap()).unwrap();
+        assert_eq!(classic.sounds.eat, None);
+        assert_eq!(classic.sounds.eat_bonus, None);
+    }
+}